@@ -0,0 +1,341 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use smol::process::Command;
+use zbus::proxy;
+
+use crate::{
+    config::{BrightnessBackend, BrightnessConfig},
+    error::Error,
+};
+
+/// Brightness VCP code of the DDC/CI protocol.
+const DDC_BRIGHTNESS: &str = "10";
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait Session {
+    fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> zbus::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum BrightnessChange {
+    /// Sets the level, `1` being the brightest.
+    To(f32),
+    /// Adds to the current level.
+    By(f32),
+}
+
+/// What the brightness is read from and written to.
+#[derive(Debug, Clone)]
+pub enum Device {
+    /// A device of the sysfs `backlight` class, written through logind so no privileges are
+    /// needed.
+    Backlight(PathBuf),
+    /// An external monitor reached over DDC/CI by `ddcutil`, on the given display or the first.
+    Ddc(Option<String>),
+}
+
+impl Device {
+    /// The configured device. `None` when no backlight was asked for and there is none, as on
+    /// most desktops.
+    pub fn open(config: &BrightnessConfig) -> Result<Option<Self>, Error> {
+        match config.backend {
+            BrightnessBackend::Ddcutil => Ok(Some(Device::Ddc(config.device.clone()))),
+            BrightnessBackend::Backlight => match &config.device {
+                Some(name) => {
+                    let dir = config.backlight_dir.join(name);
+                    if dir.is_dir() {
+                        Ok(Some(Device::Backlight(dir)))
+                    } else {
+                        Err(Error::Failed(
+                            "no such backlight device",
+                            dir.display().to_string(),
+                        ))
+                    }
+                }
+                None => {
+                    first_backlight(&config.backlight_dir).map(|dir| dir.map(Device::Backlight))
+                }
+            },
+        }
+    }
+
+    /// Files modified on every change of the level. `brightness` is written by whoever sets it,
+    /// `actual_brightness` is notified by the kernel when the firmware changes it on its own.
+    /// Monitors offer nothing to watch.
+    pub fn watched_files(&self) -> Vec<PathBuf> {
+        match self {
+            Device::Backlight(dir) => vec![dir.join("brightness"), dir.join("actual_brightness")],
+            Device::Ddc(_) => Vec::new(),
+        }
+    }
+
+    /// Level between `0` and `1`. Backlights report the level the hardware is actually at,
+    /// which may lag behind or differ from the one last written.
+    pub async fn level(&self) -> Result<f32, Error> {
+        let (current, max) = match self {
+            Device::Backlight(dir) => (
+                read_value(dir, "actual_brightness")?,
+                read_value(dir, "max_brightness")?,
+            ),
+            Device::Ddc(display) => ddc_brightness(display.as_deref()).await?,
+        };
+
+        Ok((current as f32 / max.max(1) as f32).min(1.))
+    }
+
+    pub async fn set_level(&self, level: f32) -> Result<(), Error> {
+        let level = level.clamp(0., 1.);
+
+        match self {
+            Device::Backlight(dir) => {
+                let value = (level * read_value(dir, "max_brightness")? as f32).round() as u32;
+                let name = dir.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+
+                let connection = zbus::Connection::system().await?;
+                SessionProxy::new(&connection)
+                    .await?
+                    .set_brightness("backlight", name, value)
+                    .await?;
+            }
+            Device::Ddc(display) => {
+                let (_, max) = ddc_brightness(display.as_deref()).await?;
+                let value = (level * max as f32).round() as u32;
+
+                ddcutil(
+                    display.as_deref(),
+                    &["setvcp", DDC_BRIGHTNESS, &value.to_string()],
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The device the kernel would favor: firmware interfaces over platform drivers over raw
+/// registers.
+fn first_backlight(root: &Path) -> Result<Option<PathBuf>, Error> {
+    let rank = |dir: &PathBuf| match fs::read_to_string(dir.join("type"))
+        .as_deref()
+        .map(str::trim)
+    {
+        Ok("firmware") => 0,
+        Ok("platform") => 1,
+        _ => 2,
+    };
+
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::Io("could not list the backlight devices", err)),
+    };
+
+    Ok(entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .min_by_key(rank))
+}
+
+fn read_value(dir: &Path, file: &str) -> Result<u32, Error> {
+    let content = fs::read_to_string(dir.join(file))
+        .map_err(|err| Error::Io("could not read the backlight", err))?;
+
+    content
+        .trim()
+        .parse()
+        .map_err(|_| Error::Failed("could not read the backlight", content.trim().to_string()))
+}
+
+async fn ddcutil(display: Option<&str>, args: &[&str]) -> Result<String, Error> {
+    let mut command = Command::new("ddcutil");
+    if let Some(display) = display {
+        command.args(["--display", display]);
+    }
+
+    let output = command
+        .args(args)
+        .output()
+        .await
+        .map_err(|err| Error::Io("could not run ddcutil", err))?;
+    if !output.status.success() {
+        return Err(Error::Failed(
+            "ddcutil failed",
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Current and maximum brightness of a monitor.
+async fn ddc_brightness(display: Option<&str>) -> Result<(u32, u32), Error> {
+    let output = ddcutil(display, &["getvcp", DDC_BRIGHTNESS, "--brief"]).await?;
+
+    // VCP 10 C 50 100
+    match output.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["VCP", _, "C", current, max] => {
+            current
+                .parse()
+                .ok()
+                .zip(max.parse().ok())
+                .ok_or(Error::Failed(
+                    "could not read the brightness from ddcutil",
+                    output.trim().to_string(),
+                ))
+        }
+        _ => Err(Error::Failed(
+            "could not read the brightness from ddcutil",
+            output.trim().to_string(),
+        )),
+    }
+}
+
+/// Applies a change asked for through IPC, returning the new level.
+pub async fn change(config: &BrightnessConfig, change: BrightnessChange) -> Result<f32, Error> {
+    let device = Device::open(config)?.ok_or(Error::Failed(
+        "could not change the brightness",
+        "no backlight device".into(),
+    ))?;
+
+    let level = match change {
+        BrightnessChange::To(level) => level,
+        BrightnessChange::By(step) => device.level().await? + step,
+    }
+    .clamp(0., 1.);
+    device.set_level(level).await?;
+
+    Ok(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    /// A fake `/sys/class/backlight`, removed when dropped.
+    struct Sysfs(PathBuf);
+
+    impl Sysfs {
+        fn new(test: &str) -> Self {
+            let root = env::temp_dir().join(format!("waybracelet-{}-{test}", process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+            Self(root)
+        }
+
+        fn device(&self, name: &str, files: &[(&str, &str)]) -> PathBuf {
+            let dir = self.0.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            for (file, content) in files {
+                fs::write(dir.join(file), content).unwrap();
+            }
+            dir
+        }
+
+        fn config(&self, device: Option<&str>) -> BrightnessConfig {
+            BrightnessConfig {
+                backend: BrightnessBackend::Backlight,
+                backlight_dir: self.0.clone(),
+                device: device.map(str::to_string),
+            }
+        }
+    }
+
+    impl Drop for Sysfs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn level(dir: PathBuf) -> Result<f32, Error> {
+        smol::block_on(Device::Backlight(dir).level())
+    }
+
+    #[test]
+    fn firmware_is_favored_over_platform_over_raw() {
+        let sysfs = Sysfs::new("ranking");
+        sysfs.device("a_raw", &[("type", "raw\n")]);
+        sysfs.device("b_untyped", &[]);
+        let platform = sysfs.device("c_platform", &[("type", "platform\n")]);
+        assert_eq!(first_backlight(&sysfs.0).unwrap(), Some(platform));
+
+        let firmware = sysfs.device("d_firmware", &[("type", "firmware\n")]);
+        assert_eq!(first_backlight(&sysfs.0).unwrap(), Some(firmware.clone()));
+        assert!(matches!(
+            Device::open(&sysfs.config(None)),
+            Ok(Some(Device::Backlight(dir))) if dir == firmware
+        ));
+    }
+
+    #[test]
+    fn no_backlight_is_none() {
+        let sysfs = Sysfs::new("empty");
+        assert_eq!(first_backlight(&sysfs.0).unwrap(), None);
+        assert!(matches!(Device::open(&sysfs.config(None)), Ok(None)));
+
+        let missing = sysfs.0.join("missing");
+        assert_eq!(first_backlight(&missing).unwrap(), None);
+    }
+
+    #[test]
+    fn named_device_must_exist() {
+        let sysfs = Sysfs::new("named");
+        let panel = sysfs.device("panel", &[]);
+
+        assert!(matches!(
+            Device::open(&sysfs.config(Some("panel"))),
+            Ok(Some(Device::Backlight(dir))) if dir == panel
+        ));
+        assert!(Device::open(&sysfs.config(Some("other"))).is_err());
+    }
+
+    #[test]
+    fn level_scales_the_actual_brightness() {
+        let sysfs = Sysfs::new("level");
+        let device = |name, actual: &str| {
+            sysfs.device(
+                name,
+                &[
+                    ("brightness", "0\n"),
+                    ("actual_brightness", actual),
+                    ("max_brightness", "255\n"),
+                ],
+            )
+        };
+
+        assert_eq!(level(device("off", "0\n")).unwrap(), 0.);
+        assert_eq!(level(device("half", "51\n")).unwrap(), 0.2);
+        assert_eq!(level(device("full", "255\n")).unwrap(), 1.);
+        assert_eq!(level(device("over", "300\n")).unwrap(), 1.);
+    }
+
+    #[test]
+    fn unreadable_levels_are_errors() {
+        let sysfs = Sysfs::new("garbage");
+        let device = |name, actual: &str, max: &str| {
+            sysfs.device(
+                name,
+                &[("actual_brightness", actual), ("max_brightness", max)],
+            )
+        };
+
+        assert!(level(device("text", "bright\n", "255\n")).is_err());
+        assert!(level(device("negative", "-1\n", "255\n")).is_err());
+        assert!(level(device("empty", "", "255\n")).is_err());
+        assert!(level(device("overflow", "1\n", "99999999999\n")).is_err());
+        assert!(level(sysfs.device("missing", &[("actual_brightness", "1\n")])).is_err());
+
+        let folder = sysfs.device("folder", &[("max_brightness", "255\n")]);
+        fs::create_dir(folder.join("actual_brightness")).unwrap();
+        assert!(level(folder).is_err());
+    }
+}
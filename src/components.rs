@@ -2,8 +2,9 @@ use iced::{
     Alignment::Center,
     Element,
     Length::{self},
-    widget::{Container, container, space},
+    widget::{Container, Row, container, row, space, text},
 };
+use lucide_icons::iced::icon_triangle_alert;
 
 use crate::styles;
 
@@ -15,6 +16,9 @@ impl BeadsChord {
     pub const FILL: BeadsChord = BeadsChord {
         length: Length::Fill,
     };
+    pub const W8: BeadsChord = BeadsChord {
+        length: Length::Fixed(8.),
+    };
     pub const W24: BeadsChord = BeadsChord {
         length: Length::Fixed(24.),
     };
@@ -37,3 +41,14 @@ pub fn bead<'a, T: 'a>(content: impl Into<Element<'a, T>>) -> Container<'a, T> {
 pub fn bead_center<'a, T: 'a>(content: impl Into<Element<'a, T>>) -> Container<'a, T> {
     bead(content).align_y(Center).align_x(Center)
 }
+
+/// Stands in for the content of a module whose source is unavailable. The reason itself goes to
+/// the log.
+pub fn error<'a, T: 'a>(label: Option<&'a str>) -> Row<'a, T> {
+    row![
+        icon_triangle_alert().size(24).style(text::danger),
+        label.map(|label| text(label).style(text::danger)),
+    ]
+    .spacing(8)
+    .align_y(Center)
+}
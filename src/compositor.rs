@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use iced::futures::{future::BoxFuture, stream::BoxStream};
+use serde::Deserialize;
+
+mod generic;
+mod hyprland;
+mod sway;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    pub id: i32,
+    pub name: String,
+    pub output: String,
+    /// Whether the workspace is the one shown on its output.
+    pub active: bool,
+    /// Whether any window lives in it.
+    pub occupied: bool,
+    /// Whether one of its windows asks for attention.
+    pub urgent: bool,
+    /// Scratchpad-like workspaces shown on top of the regular ones, like Hyprland's special
+    /// workspaces.
+    pub special: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    pub name: String,
+    pub focused: bool,
+    pub active_workspace: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+    pub class: String,
+    pub title: String,
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CompositorEvent {
+    /// The full list of workspaces, sent first and every time any of them changes.
+    Workspaces(Vec<Workspace>),
+    ActiveWindow(Option<Window>),
+    /// The output holding the keyboard focus, when the compositor tells.
+    FocusedOutput(Option<String>),
+}
+
+/// Workspaces grouped by output, kept up to date from the snapshots of [`Compositor::events`].
+#[derive(Debug, Default)]
+pub struct WorkspaceModel {
+    outputs: HashMap<String, Vec<Workspace>>,
+    focused: Option<String>,
+}
+
+impl WorkspaceModel {
+    /// Applies an event, returning the outputs whose workspaces changed. Workspaces created,
+    /// deleted or moved since the last snapshot show up there.
+    pub fn apply(&mut self, event: CompositorEvent) -> Vec<String> {
+        match event {
+            CompositorEvent::Workspaces(workspaces) => {
+                let mut outputs = HashMap::<_, Vec<_>>::new();
+                for workspace in workspaces {
+                    outputs
+                        .entry(workspace.output.clone())
+                        .or_default()
+                        .push(workspace);
+                }
+                outputs
+                    .values_mut()
+                    .for_each(|w| w.sort_by_key(|w| (w.special, w.id)));
+
+                let changed = self
+                    .outputs
+                    .keys()
+                    .chain(outputs.keys().filter(|o| !self.outputs.contains_key(*o)))
+                    .filter(|o| self.outputs.get(*o) != outputs.get(*o))
+                    .cloned()
+                    .collect();
+
+                self.outputs = outputs;
+                changed
+            }
+            CompositorEvent::FocusedOutput(focused) => {
+                self.focused = focused;
+                vec![]
+            }
+            CompositorEvent::ActiveWindow(_) => vec![],
+        }
+    }
+
+    /// Workspaces of an output, regular ones first, by id.
+    pub fn on(&self, output: &str) -> &[Workspace] {
+        self.outputs.get(output).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn focused(&self) -> Option<&str> {
+        self.focused.as_deref()
+    }
+}
+
+#[derive(Debug)]
+pub enum CompositorError {
+    Hyprland(String),
+    Io(std::io::Error),
+    Protocol(String),
+}
+
+impl Display for CompositorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompositorError::Hyprland(err) => write!(f, "Hyprland: {err}"),
+            CompositorError::Io(err) => write!(f, "{err}"),
+            CompositorError::Protocol(err) => write!(f, "protocol error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CompositorError {}
+
+impl From<std::io::Error> for CompositorError {
+    fn from(err: std::io::Error) -> Self {
+        CompositorError::Io(err)
+    }
+}
+
+pub type CompositorResult<T> = Result<T, CompositorError>;
+
+/// What the status bar needs from the compositor it runs on.
+pub trait Compositor: Send + Sync {
+    fn outputs(&self) -> BoxFuture<'_, CompositorResult<Vec<Output>>>;
+    fn workspaces(&self) -> BoxFuture<'_, CompositorResult<Vec<Workspace>>>;
+    fn active_window(&self) -> BoxFuture<'_, CompositorResult<Option<Window>>>;
+    fn focus_workspace<'a>(
+        &'a self,
+        workspace: &'a Workspace,
+    ) -> BoxFuture<'a, CompositorResult<()>>;
+
+    /// Streams the compositor state, starting with a snapshot of it.
+    fn events(&self) -> BoxStream<'static, CompositorResult<CompositorEvent>>;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Picks the backend from the environment the daemon was started in.
+    #[default]
+    Auto,
+    Hyprland,
+    Sway,
+    /// `ext-workspace-v1` and `wlr-foreign-toplevel-management`, for any other compositor.
+    Generic,
+}
+
+impl Backend {
+    pub fn resolve(self) -> Self {
+        match self {
+            Backend::Auto if std::env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() => {
+                Backend::Hyprland
+            }
+            Backend::Auto if std::env::var_os("SWAYSOCK").is_some() => Backend::Sway,
+            Backend::Auto => Backend::Generic,
+            backend => backend,
+        }
+    }
+
+    pub fn connect(self) -> Box<dyn Compositor> {
+        match self.resolve() {
+            Backend::Hyprland => Box::new(hyprland::Hyprland),
+            Backend::Sway => Box::new(sway::Sway::from_env()),
+            Backend::Auto | Backend::Generic => Box::new(generic::Generic),
+        }
+    }
+}
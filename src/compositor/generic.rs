@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+
+use iced::futures::{FutureExt, StreamExt, future::BoxFuture, stream::BoxStream};
+use wayland_client::{
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+    backend::ObjectId,
+    event_created_child,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::{
+        wl_output::{self, WlOutput},
+        wl_registry::{self, WlRegistry},
+    },
+};
+use wayland_protocols::ext::workspace::v1::client::{
+    ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
+    ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
+    ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+};
+use wayland_protocols_wlr::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+use super::{
+    Compositor, CompositorError, CompositorEvent, CompositorResult, Output, Window, Workspace,
+};
+
+/// Works on any compositor implementing `ext-workspace-v1` and
+/// `wlr-foreign-toplevel-management`, through its own Wayland connection. Missing protocols
+/// just leave the matching data empty.
+pub struct Generic;
+
+#[derive(Default)]
+struct Group {
+    outputs: Vec<ObjectId>,
+    workspaces: Vec<ObjectId>,
+}
+
+struct WorkspaceState {
+    /// Order in which the compositor announced it, used when the name is not a number.
+    order: i32,
+    handle: ExtWorkspaceHandleV1,
+    name: String,
+    active: bool,
+    urgent: bool,
+    hidden: bool,
+}
+
+#[derive(Default)]
+struct Toplevel {
+    title: String,
+    app_id: String,
+    activated: bool,
+    outputs: Vec<ObjectId>,
+}
+
+#[derive(Default)]
+struct State {
+    workspace_manager: Option<ExtWorkspaceManagerV1>,
+    outputs: HashMap<ObjectId, String>,
+    groups: HashMap<ObjectId, Group>,
+    workspaces: HashMap<ObjectId, WorkspaceState>,
+    toplevels: HashMap<ObjectId, Toplevel>,
+    announced: i32,
+    changed: bool,
+}
+
+impl State {
+    fn connect() -> CompositorResult<(EventQueue<State>, State)> {
+        let connection = Connection::connect_to_env().map_err(protocol_error)?;
+        let (globals, mut queue) =
+            registry_queue_init::<State>(&connection).map_err(protocol_error)?;
+        let qh = queue.handle();
+
+        globals.contents().with_list(|list| {
+            list.iter()
+                .filter(|g| g.interface == WlOutput::interface().name)
+                .for_each(|g| {
+                    globals
+                        .registry()
+                        .bind::<WlOutput, _, _>(g.name, g.version.min(4), &qh, ());
+                });
+        });
+        // Either protocol may be missing, the other one still provides its half.
+        let workspace_manager = globals
+            .bind::<ExtWorkspaceManagerV1, _, _>(&qh, 1..=1, ())
+            .ok();
+        let _ = globals.bind::<ZwlrForeignToplevelManagerV1, _, _>(&qh, 1..=3, ());
+
+        let mut state = State {
+            workspace_manager,
+            ..Default::default()
+        };
+        // The first roundtrip announces the objects, the second one their properties.
+        queue.roundtrip(&mut state).map_err(protocol_error)?;
+        queue.roundtrip(&mut state).map_err(protocol_error)?;
+
+        Ok((queue, state))
+    }
+
+    fn output_name(&self, ids: &[ObjectId]) -> Option<String> {
+        ids.iter().find_map(|id| self.outputs.get(id)).cloned()
+    }
+
+    fn workspaces(&self) -> Vec<Workspace> {
+        let mut workspaces = self
+            .groups
+            .values()
+            .flat_map(|group| {
+                let output = self.output_name(&group.outputs).unwrap_or_default();
+                group.workspaces.iter().filter_map(move |id| {
+                    let workspace = self.workspaces.get(id).filter(|w| !w.hidden)?;
+                    Some(Workspace {
+                        id: workspace.name.parse().unwrap_or(workspace.order),
+                        name: workspace.name.clone(),
+                        output: output.clone(),
+                        active: workspace.active,
+                        // Foreign toplevels do not tell which workspace they live in.
+                        occupied: false,
+                        urgent: workspace.urgent,
+                        special: false,
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        workspaces.sort_by_key(|w| w.id);
+
+        workspaces
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        let workspaces = self.workspaces();
+
+        self.outputs
+            .values()
+            .map(|name| Output {
+                name: name.clone(),
+                focused: false,
+                active_workspace: workspaces
+                    .iter()
+                    .find(|w| w.active && w.output == *name)
+                    .map(|w| w.id),
+            })
+            .collect()
+    }
+
+    fn active_window(&self) -> Option<Window> {
+        self.toplevels
+            .values()
+            .find(|t| t.activated)
+            .map(|t| Window {
+                class: t.app_id.clone(),
+                title: t.title.clone(),
+                output: self.output_name(&t.outputs),
+            })
+    }
+}
+
+fn protocol_error(err: impl std::fmt::Display) -> CompositorError {
+    CompositorError::Protocol(err.to_string())
+}
+
+async fn snapshot() -> CompositorResult<State> {
+    smol::unblock(|| State::connect().map(|(_, state)| state)).await
+}
+
+impl Compositor for Generic {
+    fn outputs(&self) -> BoxFuture<'_, CompositorResult<Vec<Output>>> {
+        async { Ok(snapshot().await?.outputs()) }.boxed()
+    }
+
+    fn workspaces(&self) -> BoxFuture<'_, CompositorResult<Vec<Workspace>>> {
+        async { Ok(snapshot().await?.workspaces()) }.boxed()
+    }
+
+    fn active_window(&self) -> BoxFuture<'_, CompositorResult<Option<Window>>> {
+        async { Ok(snapshot().await?.active_window()) }.boxed()
+    }
+
+    fn focus_workspace<'a>(
+        &'a self,
+        workspace: &'a Workspace,
+    ) -> BoxFuture<'a, CompositorResult<()>> {
+        let name = workspace.name.clone();
+
+        smol::unblock(move || {
+            let (mut queue, mut state) = State::connect()?;
+            let manager = state.workspace_manager.clone().ok_or_else(|| {
+                CompositorError::Protocol("ext-workspace-v1 is not supported".into())
+            })?;
+            let handle = state
+                .workspaces
+                .values()
+                .find(|w| w.name == name)
+                .map(|w| w.handle.clone())
+                .ok_or_else(|| CompositorError::Protocol(format!("no workspace named {name}")))?;
+
+            handle.activate();
+            manager.commit();
+            queue.roundtrip(&mut state).map_err(protocol_error)?;
+
+            Ok(())
+        })
+        .boxed()
+    }
+
+    fn events(&self) -> BoxStream<'static, CompositorResult<CompositorEvent>> {
+        let (sender, receiver) = smol::channel::unbounded();
+
+        // Dispatching blocks, so the connection gets its own thread. It ends once the receiving
+        // side is dropped.
+        std::thread::spawn(move || {
+            let (mut queue, mut state) = match State::connect() {
+                Ok(connected) => connected,
+                Err(err) => {
+                    let _ = sender.send_blocking(Err(err));
+                    return;
+                }
+            };
+            state.changed = true;
+
+            loop {
+                if std::mem::take(&mut state.changed) {
+                    let events = [
+                        CompositorEvent::Workspaces(state.workspaces()),
+                        CompositorEvent::ActiveWindow(state.active_window()),
+                    ];
+                    if events
+                        .into_iter()
+                        .any(|e| sender.send_blocking(Ok(e)).is_err())
+                    {
+                        return;
+                    }
+                }
+
+                if let Err(err) = queue.blocking_dispatch(&mut state) {
+                    let _ = sender.send_blocking(Err(protocol_error(err)));
+                    return;
+                }
+            }
+        });
+
+        receiver.boxed()
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlOutput, ()> for State {
+    fn event(
+        state: &mut Self,
+        output: &WlOutput,
+        event: wl_output::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_output::Event::Name { name } = event {
+            state.outputs.insert(output.id(), name);
+            state.changed = true;
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ExtWorkspaceManagerV1,
+        event: ext_workspace_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            ext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
+                state.groups.insert(workspace_group.id(), Group::default());
+            }
+            ext_workspace_manager_v1::Event::Workspace { workspace } => {
+                state.announced += 1;
+                state.workspaces.insert(
+                    workspace.id(),
+                    WorkspaceState {
+                        order: state.announced,
+                        handle: workspace,
+                        name: String::new(),
+                        active: false,
+                        urgent: false,
+                        hidden: false,
+                    },
+                );
+            }
+            ext_workspace_manager_v1::Event::Done => state.changed = true,
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ExtWorkspaceManagerV1, [
+        ext_workspace_manager_v1::EVT_WORKSPACE_GROUP_OPCODE => (ExtWorkspaceGroupHandleV1, ()),
+        ext_workspace_manager_v1::EVT_WORKSPACE_OPCODE => (ExtWorkspaceHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ExtWorkspaceGroupHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ExtWorkspaceGroupHandleV1,
+        event: ext_workspace_group_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_workspace_group_handle_v1::Event::Removed = event {
+            state.groups.remove(&handle.id());
+            return;
+        }
+
+        let Some(group) = state.groups.get_mut(&handle.id()) else {
+            return;
+        };
+
+        match event {
+            ext_workspace_group_handle_v1::Event::OutputEnter { output } => {
+                group.outputs.push(output.id());
+            }
+            ext_workspace_group_handle_v1::Event::OutputLeave { output } => {
+                group.outputs.retain(|id| *id != output.id());
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceEnter { workspace } => {
+                group.workspaces.push(workspace.id());
+            }
+            ext_workspace_group_handle_v1::Event::WorkspaceLeave { workspace } => {
+                group.workspaces.retain(|id| *id != workspace.id());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ExtWorkspaceHandleV1,
+        event: ext_workspace_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let ext_workspace_handle_v1::Event::Removed = event {
+            state.workspaces.remove(&handle.id());
+            return;
+        }
+
+        let Some(workspace) = state.workspaces.get_mut(&handle.id()) else {
+            return;
+        };
+
+        match event {
+            ext_workspace_handle_v1::Event::Name { name } => workspace.name = name,
+            ext_workspace_handle_v1::Event::State {
+                state: WEnum::Value(flags),
+            } => {
+                workspace.active = flags.contains(ext_workspace_handle_v1::State::Active);
+                workspace.urgent = flags.contains(ext_workspace_handle_v1::State::Urgent);
+                workspace.hidden = flags.contains(ext_workspace_handle_v1::State::Hidden);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrForeignToplevelManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrForeignToplevelManagerV1,
+        event: zwlr_foreign_toplevel_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+            state.toplevels.insert(toplevel.id(), Toplevel::default());
+        }
+    }
+
+    event_created_child!(State, ZwlrForeignToplevelManagerV1, [
+        zwlr_foreign_toplevel_manager_v1::EVT_TOPLEVEL_OPCODE => (ZwlrForeignToplevelHandleV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            state.toplevels.remove(&handle.id());
+            state.changed = true;
+            return;
+        }
+
+        let Some(toplevel) = state.toplevels.get_mut(&handle.id()) else {
+            return;
+        };
+
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::Title { title } => toplevel.title = title,
+            zwlr_foreign_toplevel_handle_v1::Event::AppId { app_id } => toplevel.app_id = app_id,
+            zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => {
+                toplevel.outputs.push(output.id());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputLeave { output } => {
+                toplevel.outputs.retain(|id| *id != output.id());
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state: flags } => {
+                let activated = zwlr_foreign_toplevel_handle_v1::State::Activated as u32;
+                toplevel.activated = flags
+                    .chunks_exact(4)
+                    .any(|f| u32::from_ne_bytes([f[0], f[1], f[2], f[3]]) == activated);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::Done => state.changed = true,
+            _ => {}
+        }
+    }
+}
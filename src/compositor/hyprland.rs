@@ -0,0 +1,191 @@
+use std::{collections::HashSet, fmt::Display, pin::pin};
+
+use ::hyprland::{
+    data::{Client, Clients, Monitors, Workspaces},
+    dispatch::{Dispatch, DispatchType, WorkspaceIdentifierWithSpecial},
+    event_listener::{Event, EventStream},
+    shared::{Address, HyprData, HyprDataActiveOptional},
+};
+use iced::{
+    futures::{FutureExt, SinkExt, StreamExt, future::BoxFuture, stream::BoxStream},
+    stream,
+};
+
+use super::{
+    Compositor, CompositorError, CompositorEvent, CompositorResult, Output, Window, Workspace,
+};
+
+pub struct Hyprland;
+
+fn hypr_error(err: impl Display) -> CompositorError {
+    CompositorError::Hyprland(err.to_string())
+}
+
+/// Special workspaces have negative ids and names prefixed with `special:`.
+const SPECIAL_PREFIX: &str = "special:";
+
+async fn workspaces(urgent: &HashSet<i32>) -> CompositorResult<Vec<Workspace>> {
+    let monitors = Monitors::get_async().await.map_err(hypr_error)?;
+    let workspaces = Workspaces::get_async().await.map_err(hypr_error)?;
+
+    Ok(workspaces
+        .into_iter()
+        .map(|w| Workspace {
+            active: monitors.iter().any(|m| {
+                m.name == w.monitor
+                    && (m.active_workspace.id == w.id || m.special_workspace.id == w.id)
+            }),
+            occupied: w.windows > 0,
+            urgent: urgent.contains(&w.id),
+            special: w.id < 0,
+            id: w.id,
+            name: w.name,
+            output: w.monitor,
+        })
+        .collect())
+}
+
+async fn focused_output() -> CompositorResult<Option<String>> {
+    Ok(Monitors::get_async()
+        .await
+        .map_err(hypr_error)?
+        .into_iter()
+        .find(|m| m.focused)
+        .map(|m| m.name))
+}
+
+/// Hyprland only tells which window became urgent, so the workspace is looked up from it.
+async fn workspace_of(address: &Address) -> CompositorResult<Option<i32>> {
+    Ok(Clients::get_async()
+        .await
+        .map_err(hypr_error)?
+        .into_iter()
+        .find(|c| c.address == *address)
+        .map(|c| c.workspace.id))
+}
+
+async fn active_window() -> CompositorResult<Option<Window>> {
+    let Some(client) = Client::get_active_async().await.map_err(hypr_error)? else {
+        return Ok(None);
+    };
+
+    let output = Workspaces::get_async()
+        .await
+        .map_err(hypr_error)?
+        .into_iter()
+        .find(|w| w.id == client.workspace.id)
+        .map(|w| w.monitor);
+
+    Ok(Some(Window {
+        class: client.class,
+        title: client.title,
+        output,
+    }))
+}
+
+impl Compositor for Hyprland {
+    fn outputs(&self) -> BoxFuture<'_, CompositorResult<Vec<Output>>> {
+        async {
+            Ok(Monitors::get_async()
+                .await
+                .map_err(hypr_error)?
+                .into_iter()
+                .map(|m| Output {
+                    name: m.name,
+                    focused: m.focused,
+                    active_workspace: Some(m.active_workspace.id),
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn workspaces(&self) -> BoxFuture<'_, CompositorResult<Vec<Workspace>>> {
+        async { workspaces(&HashSet::new()).await }.boxed()
+    }
+
+    fn active_window(&self) -> BoxFuture<'_, CompositorResult<Option<Window>>> {
+        active_window().boxed()
+    }
+
+    fn focus_workspace<'a>(
+        &'a self,
+        workspace: &'a Workspace,
+    ) -> BoxFuture<'a, CompositorResult<()>> {
+        async {
+            let dispatch = match workspace.name.strip_prefix(SPECIAL_PREFIX) {
+                Some(name) if workspace.special => {
+                    DispatchType::ToggleSpecialWorkspace(Some(name.to_string()))
+                }
+                _ => DispatchType::Workspace(WorkspaceIdentifierWithSpecial::Id(workspace.id)),
+            };
+
+            Dispatch::call_async(dispatch).await.map_err(hypr_error)
+        }
+        .boxed()
+    }
+
+    fn events(&self) -> BoxStream<'static, CompositorResult<CompositorEvent>> {
+        stream::channel(8, async |mut output| {
+            // Urgency is only announced, never withdrawn, so it lasts until the workspace is shown.
+            let mut urgent = HashSet::new();
+
+            let snapshot = [
+                workspaces(&urgent).await.map(CompositorEvent::Workspaces),
+                focused_output().await.map(CompositorEvent::FocusedOutput),
+                active_window().await.map(CompositorEvent::ActiveWindow),
+            ];
+            for event in snapshot {
+                if output.send(event).await.is_err() {
+                    return;
+                }
+            }
+
+            let mut events = pin!(EventStream::new());
+            while let Some(event) = events.next().await {
+                let event = match event {
+                    Ok(Event::ActiveMonitorChanged(m)) => {
+                        Ok(CompositorEvent::FocusedOutput(Some(m.monitor_name)))
+                    }
+                    Ok(Event::ActiveWindowChanged(_) | Event::WindowTitleChanged(_)) => {
+                        active_window().await.map(CompositorEvent::ActiveWindow)
+                    }
+                    Ok(Event::WorkspaceChanged(w)) => {
+                        urgent.remove(&w.id);
+                        workspaces(&urgent).await.map(CompositorEvent::Workspaces)
+                    }
+                    Ok(Event::WorkspaceDeleted(w)) => {
+                        urgent.remove(&w.id);
+                        workspaces(&urgent).await.map(CompositorEvent::Workspaces)
+                    }
+                    Ok(Event::UrgentStateChanged(address)) => match workspace_of(&address).await {
+                        Ok(id) => {
+                            urgent.extend(id);
+                            workspaces(&urgent).await.map(CompositorEvent::Workspaces)
+                        }
+                        Err(err) => Err(err),
+                    },
+                    // Any of these may add, remove or move a workspace, or change what it holds.
+                    Ok(
+                        Event::WorkspaceAdded(_)
+                        | Event::WorkspaceMoved(_)
+                        | Event::WorkspaceRenamed(_)
+                        | Event::MonitorAdded(_)
+                        | Event::MonitorRemoved(_)
+                        | Event::ChangedSpecial(_)
+                        | Event::WindowOpened(_)
+                        | Event::WindowClosed(_)
+                        | Event::WindowMoved(_),
+                    ) => workspaces(&urgent).await.map(CompositorEvent::Workspaces),
+                    Ok(_) => continue,
+                    Err(err) => Err(hypr_error(err)),
+                };
+
+                if output.send(event).await.is_err() {
+                    return;
+                }
+            }
+        })
+        .boxed()
+    }
+}
@@ -0,0 +1,331 @@
+use std::{io, path::PathBuf};
+
+use iced::{
+    futures::{FutureExt, SinkExt, channel::mpsc::Sender, future::BoxFuture, stream::BoxStream},
+    stream,
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use smol::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::unix::UnixStream,
+};
+
+use super::{
+    Compositor, CompositorError, CompositorEvent, CompositorResult, Output, Window, Workspace,
+};
+
+const MAGIC: &[u8] = b"i3-ipc";
+
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_OUTPUTS: u32 = 3;
+const GET_TREE: u32 = 4;
+
+/// Ids given to named workspaces start here, past any workspace number, so they sort after the
+/// numbered ones as in Sway.
+const NAMED_WORKSPACES: i32 = 1 << 30;
+
+/// Events have the highest bit of their type set.
+const WORKSPACE_EVENT: u32 = 0x8000_0000;
+
+/// Talks the i3 IPC protocol spoken by Sway over `$SWAYSOCK`.
+pub struct Sway {
+    socket: Option<PathBuf>,
+}
+
+impl Sway {
+    pub fn from_env() -> Self {
+        Self {
+            socket: std::env::var_os("SWAYSOCK").map(PathBuf::from),
+        }
+    }
+
+    async fn connect(&self) -> CompositorResult<Ipc> {
+        let socket = self
+            .socket
+            .as_ref()
+            .ok_or_else(|| CompositorError::Protocol("SWAYSOCK is not set".into()))?;
+
+        Ok(Ipc(UnixStream::connect(socket).await?))
+    }
+}
+
+struct Ipc(UnixStream);
+
+impl Ipc {
+    async fn send(&mut self, kind: u32, payload: &[u8]) -> io::Result<()> {
+        let mut message = Vec::with_capacity(MAGIC.len() + 8 + payload.len());
+        message.extend_from_slice(MAGIC);
+        message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(payload);
+
+        self.0.write_all(&message).await
+    }
+
+    async fn receive(&mut self) -> CompositorResult<(u32, Vec<u8>)> {
+        let mut header = [0; 14];
+        self.0.read_exact(&mut header).await?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(CompositorError::Protocol("bad i3-ipc magic string".into()));
+        }
+
+        let len = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+        let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+
+        let mut payload = vec![0; len as usize];
+        self.0.read_exact(&mut payload).await?;
+
+        Ok((kind, payload))
+    }
+
+    async fn request<T>(&mut self, kind: u32, payload: &[u8]) -> CompositorResult<T>
+    where
+        T: DeserializeOwned,
+    {
+        self.send(kind, payload).await?;
+        let (_, reply) = self.receive().await?;
+
+        serde_json::from_slice(&reply).map_err(|err| CompositorError::Protocol(err.to_string()))
+    }
+
+    async fn workspaces(&mut self) -> CompositorResult<Vec<Workspace>> {
+        let workspaces: Vec<SwayWorkspace> = self.request(GET_WORKSPACES, b"").await?;
+
+        Ok(workspaces.into_iter().map(Into::into).collect())
+    }
+}
+
+#[derive(Deserialize)]
+struct SwayWorkspace {
+    /// Id of the tree node, unique and growing with each new workspace.
+    id: i64,
+    /// Number the name starts with, `-1` for names without one.
+    num: i32,
+    name: String,
+    output: String,
+    visible: bool,
+    urgent: bool,
+    /// Layout of the windows in the workspace, like `H[foot firefox]`. Unset when it is empty.
+    representation: Option<String>,
+}
+
+impl From<SwayWorkspace> for Workspace {
+    fn from(w: SwayWorkspace) -> Self {
+        Workspace {
+            id: match w.num {
+                -1 => {
+                    i32::try_from(w.id).map_or(i32::MAX, |id| NAMED_WORKSPACES.saturating_add(id))
+                }
+                num => num,
+            },
+            name: w.name,
+            output: w.output,
+            active: w.visible,
+            occupied: w.representation.is_some_and(|r| !r.is_empty()),
+            urgent: w.urgent,
+            special: false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct WorkspaceEvent {
+    change: String,
+    current: Option<WorkspaceNode>,
+}
+
+/// Events carry tree nodes, which lack some of the fields of `GET_WORKSPACES`.
+#[derive(Deserialize)]
+struct WorkspaceNode {
+    output: String,
+}
+
+#[derive(Deserialize)]
+struct CommandReply {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    name: String,
+    focused: bool,
+    current_workspace: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SwayNode {
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<String>,
+    focused: bool,
+    app_id: Option<String>,
+    window_properties: Option<WindowProperties>,
+    #[serde(default)]
+    nodes: Vec<SwayNode>,
+    #[serde(default)]
+    floating_nodes: Vec<SwayNode>,
+}
+
+#[derive(Deserialize)]
+struct WindowProperties {
+    class: Option<String>,
+}
+
+impl SwayNode {
+    /// Looks for the focused window, remembering the output it lives in.
+    fn focused_window(&self, output: Option<&str>) -> Option<Window> {
+        let output = if self.kind == "output" {
+            self.name.as_deref()
+        } else {
+            output
+        };
+
+        if self.focused && matches!(self.kind.as_str(), "con" | "floating_con") {
+            return Some(Window {
+                class: self
+                    .app_id
+                    .clone()
+                    .or_else(|| self.window_properties.as_ref()?.class.clone())
+                    .unwrap_or_default(),
+                title: self.name.clone().unwrap_or_default(),
+                output: output.map(str::to_string),
+            });
+        }
+
+        self.nodes
+            .iter()
+            .chain(&self.floating_nodes)
+            .find_map(|n| n.focused_window(output))
+    }
+}
+
+impl Compositor for Sway {
+    fn outputs(&self) -> BoxFuture<'_, CompositorResult<Vec<Output>>> {
+        async {
+            let mut ipc = self.connect().await?;
+            let workspaces = ipc.workspaces().await?;
+            let outputs: Vec<SwayOutput> = ipc.request(GET_OUTPUTS, b"").await?;
+
+            Ok(outputs
+                .into_iter()
+                .map(|o| Output {
+                    active_workspace: workspaces
+                        .iter()
+                        .find(|w| Some(&w.name) == o.current_workspace.as_ref())
+                        .map(|w| w.id),
+                    name: o.name,
+                    focused: o.focused,
+                })
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn workspaces(&self) -> BoxFuture<'_, CompositorResult<Vec<Workspace>>> {
+        async { self.connect().await?.workspaces().await }.boxed()
+    }
+
+    fn active_window(&self) -> BoxFuture<'_, CompositorResult<Option<Window>>> {
+        async {
+            let tree: SwayNode = self.connect().await?.request(GET_TREE, b"").await?;
+            Ok(tree.focused_window(None))
+        }
+        .boxed()
+    }
+
+    fn focus_workspace<'a>(
+        &'a self,
+        workspace: &'a Workspace,
+    ) -> BoxFuture<'a, CompositorResult<()>> {
+        async {
+            // Quoted, as names may contain spaces.
+            let command = format!("workspace \"{}\"", workspace.name.replace('"', "\\\""));
+            let replies: Vec<CommandReply> = self
+                .connect()
+                .await?
+                .request(RUN_COMMAND, command.as_bytes())
+                .await?;
+
+            match replies.into_iter().find_map(|r| r.error) {
+                Some(err) => Err(CompositorError::Protocol(err)),
+                None => Ok(()),
+            }
+        }
+        .boxed()
+    }
+
+    fn events(&self) -> BoxStream<'static, CompositorResult<CompositorEvent>> {
+        let sway = Sway {
+            socket: self.socket.clone(),
+        };
+
+        stream::channel(8, async move |mut output| {
+            if let Err(err) = forward_events(&sway, &mut output).await {
+                let _ = output.send(Err(err)).await;
+            }
+        })
+        .boxed()
+    }
+}
+
+async fn forward_events(
+    sway: &Sway,
+    output: &mut Sender<CompositorResult<CompositorEvent>>,
+) -> CompositorResult<()> {
+    // Replies come in the order of the requests, so queries go through their own connection
+    // while this one only carries events.
+    let mut queries = sway.connect().await?;
+    let mut events = sway.connect().await?;
+
+    let subscribed: serde_json::Value = events
+        .request(SUBSCRIBE, br#"["workspace", "window"]"#)
+        .await?;
+    if subscribed["success"] != true {
+        return Err(CompositorError::Protocol(
+            "could not subscribe to events".into(),
+        ));
+    }
+
+    let outputs: Vec<SwayOutput> = queries.request(GET_OUTPUTS, b"").await?;
+    let focused = outputs.into_iter().find(|o| o.focused).map(|o| o.name);
+    if output
+        .send(Ok(CompositorEvent::FocusedOutput(focused)))
+        .await
+        .is_err()
+    {
+        return Ok(());
+    }
+
+    loop {
+        let workspaces = queries.workspaces().await?;
+        let tree: SwayNode = queries.request(GET_TREE, b"").await?;
+
+        let snapshot = [
+            CompositorEvent::Workspaces(workspaces),
+            CompositorEvent::ActiveWindow(tree.focused_window(None)),
+        ];
+        for event in snapshot {
+            if output.send(Ok(event)).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        let (kind, payload) = events.receive().await?;
+        if kind != WORKSPACE_EVENT {
+            continue;
+        }
+
+        let event: WorkspaceEvent = serde_json::from_slice(&payload)
+            .map_err(|err| CompositorError::Protocol(err.to_string()))?;
+        if let ("focus", Some(current)) = (event.change.as_str(), event.current)
+            && output
+                .send(Ok(CompositorEvent::FocusedOutput(Some(current.output))))
+                .await
+                .is_err()
+        {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,654 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    hash::{Hash, Hasher},
+    io, iter,
+    os::fd::AsFd,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iced::{
+    Subscription,
+    futures::{SinkExt, channel::mpsc::Sender},
+    stream,
+};
+use inotify::{EventMask, Inotify, WatchMask};
+use serde::Deserialize;
+use smol::Async;
+
+use crate::{
+    Message,
+    compositor::Backend,
+    features::{
+        notifications::{ExpireTimeout, Urgency},
+        status_bar::Module,
+    },
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// File where the daemon writes its logs, besides stderr.
+    pub log_file: Option<PathBuf>,
+    pub theme: ThemeSetting,
+    /// Compositor to query for workspaces and windows. Detected from the environment by default.
+    pub compositor: Backend,
+    pub status_bar: StatusBarConfig,
+    /// Formerly `volume_osd`, still read under that name.
+    #[serde(alias = "volume_osd")]
+    pub osd: OsdConfig,
+    pub brightness: BrightnessConfig,
+    pub notifications: NotificationsConfig,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeSetting {
+    #[default]
+    Dark,
+    Light,
+    /// Follows the color scheme preference of the desktop portal.
+    System,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThemeVariant {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl ThemeVariant {
+    pub fn toggled(self) -> Self {
+        match self {
+            ThemeVariant::Dark => ThemeVariant::Light,
+            ThemeVariant::Light => ThemeVariant::Dark,
+        }
+    }
+}
+
+impl ThemeSetting {
+    /// The variant to use before the portal, a signal or an IPC command says otherwise.
+    pub fn initial_variant(self) -> ThemeVariant {
+        match self {
+            ThemeSetting::Light => ThemeVariant::Light,
+            ThemeSetting::Dark | ThemeSetting::System => ThemeVariant::Dark,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatusBarConfig {
+    /// Output whose bar shows the power button. Every bar shows it when unset.
+    pub main_output: Option<String>,
+    /// Characters of the focused window's title, and of the track's title and artist, shown
+    /// before they are ellipsized.
+    pub max_title_length: usize,
+    pub layout: Layout,
+    /// Layouts replacing `layout` on the bars of specific outputs, keyed by output name.
+    pub outputs: HashMap<String, Layout>,
+    pub visualizer: VisualizerConfig,
+}
+
+impl StatusBarConfig {
+    pub fn layout(&self, output: &str) -> &Layout {
+        self.outputs.get(output).unwrap_or(&self.layout)
+    }
+}
+
+impl Default for StatusBarConfig {
+    fn default() -> Self {
+        Self {
+            main_output: None,
+            max_title_length: 48,
+            layout: Layout {
+                left: vec![
+                    Slot::Module(Module::Workspaces),
+                    Slot::Module(Module::Cava),
+                    Slot::Module(Module::Media),
+                ],
+                center: vec![],
+                right: vec![
+                    Slot::Module(Module::Tray),
+                    Slot::Group(vec![Module::Clock, Module::Power]),
+                ],
+                waves: Waves::default(),
+            },
+            outputs: HashMap::new(),
+            visualizer: VisualizerConfig::default(),
+        }
+    }
+}
+
+/// The audio analysis behind the `cava` module, shared by every bar.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisualizerConfig {
+    /// Bands the spectrum is split into. Bars resample them to the number of waves they draw.
+    pub bars: usize,
+    /// Share of the previous height a bar keeps when it rises, from `0` to just below `1`.
+    pub smoothing: f32,
+    /// Height a bar can lose from one frame to the next, as a fraction of the full height.
+    pub falloff: f32,
+    pub input: AudioInput,
+}
+
+impl Default for VisualizerConfig {
+    fn default() -> Self {
+        Self {
+            bars: 24,
+            smoothing: 0.5,
+            falloff: 0.05,
+            input: AudioInput::default(),
+        }
+    }
+}
+
+impl VisualizerConfig {
+    const MAX_BARS: usize = 128;
+}
+
+/// Identifies the analysis the configuration asks for, to share it between bars.
+impl Hash for VisualizerConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bars.hash(state);
+        self.smoothing.to_bits().hash(state);
+        self.falloff.to_bits().hash(state);
+        self.input.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioInput {
+    /// What the default output plays, recorded through PipeWire.
+    #[default]
+    PipeWire,
+    /// Signed 16-bit little-endian mono PCM at 48kHz, read from a file or a FIFO.
+    File(PathBuf),
+}
+
+/// Modules of a bar, from left to right within each section, and how it draws the waves of the
+/// visualizer. Sections left out are empty.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    pub left: Vec<Slot>,
+    pub center: Vec<Slot>,
+    pub right: Vec<Slot>,
+    pub waves: Waves,
+}
+
+/// How a bar draws the waves of the visualizer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Waves {
+    /// Waves drawn. As many as fit in the room left on the bar when unset.
+    pub count: Option<usize>,
+    /// Spreads the spectrum from the middle out, the lowest notes in the middle.
+    pub mirrored: bool,
+}
+
+impl Layout {
+    pub fn contains(&self, module: Module) -> bool {
+        [&self.left, &self.center, &self.right]
+            .into_iter()
+            .flatten()
+            .any(|slot| slot.contains(module))
+    }
+}
+
+/// A module on a bead of its own, or a list of them sharing one.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Slot {
+    Module(Module),
+    Group(Vec<Module>),
+}
+
+impl Slot {
+    pub fn contains(&self, module: Module) -> bool {
+        match self {
+            Slot::Module(m) => *m == module,
+            Slot::Group(modules) => modules.contains(&module),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OsdConfig {
+    /// `"focused"`, `"all"` or the name of an output.
+    pub output: OsdOutput,
+    /// Lets the pointer keep the OSD up by hovering it, change the level by scrolling or dragging
+    /// along the bar, and mute by clicking the volume icon.
+    pub interactive: bool,
+}
+
+/// Where the OSD appears.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum OsdOutput {
+    /// The output holding the keyboard focus. The compositor chooses one when it does not tell.
+    #[default]
+    Focused,
+    /// Every connected output at once.
+    All,
+    /// A given output. The compositor chooses one while it is not connected.
+    Named(String),
+}
+
+impl From<String> for OsdOutput {
+    fn from(output: String) -> Self {
+        match output.as_str() {
+            "focused" => OsdOutput::Focused,
+            "all" => OsdOutput::All,
+            _ => OsdOutput::Named(output),
+        }
+    }
+}
+
+/// The display whose brightness the OSD follows and IPC commands change.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrightnessConfig {
+    pub backend: BrightnessBackend,
+    /// Where backlight devices are listed, replaceable by a fake one.
+    pub backlight_dir: PathBuf,
+    /// Backlight device, or `ddcutil` display number. The kernel's preferred backlight, or the
+    /// first display, when unset.
+    pub device: Option<String>,
+}
+
+impl Default for BrightnessConfig {
+    fn default() -> Self {
+        Self {
+            backend: BrightnessBackend::default(),
+            backlight_dir: PathBuf::from("/sys/class/backlight"),
+            device: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrightnessBackend {
+    /// Built-in panels, through the kernel's backlight class.
+    #[default]
+    Backlight,
+    /// External monitors over DDC/CI, through `ddcutil`.
+    Ddcutil,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsConfig {
+    /// Output where notifications appear. The compositor chooses one when unset.
+    pub output: Option<String>,
+    pub width: u32,
+    /// Timeout in milliseconds for notifications that ask for the server default.
+    pub default_timeout: u64,
+    /// Same as `default_timeout`, for low urgency notifications.
+    pub low_timeout: u64,
+    /// Timeout in milliseconds for critical notifications, overriding the one they ask for.
+    /// They stay until closed when unset, as the specification recommends.
+    pub critical_timeout: Option<u64>,
+}
+
+impl Default for NotificationsConfig {
+    fn default() -> Self {
+        Self {
+            output: None,
+            width: 500,
+            default_timeout: 5000,
+            low_timeout: 2500,
+            critical_timeout: None,
+        }
+    }
+}
+
+impl NotificationsConfig {
+    const MIN_WIDTH: u32 = 200;
+
+    /// How long a notification stays, `None` until it is closed.
+    pub fn timeout(&self, urgency: Urgency, asked: ExpireTimeout) -> Option<Duration> {
+        match urgency {
+            Urgency::Low => asked.duration(Duration::from_millis(self.low_timeout)),
+            Urgency::Normal => asked.duration(Duration::from_millis(self.default_timeout)),
+            Urgency::Critical => self.critical_timeout.map(Duration::from_millis),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Invalid(&'static str, String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => {
+                write!(f, "could not read {}: {err}", path.display())
+            }
+            ConfigError::Parse(path, err) => {
+                write!(f, "could not parse {}: {err}", path.display())
+            }
+            ConfigError::Invalid(field, reason) => write!(f, "invalid `{field}`: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/waybracelet/config.toml`, falling back to `~/.config`.
+    pub fn path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .map(|d| d.join("waybracelet").join("config.toml"))
+    }
+
+    /// Loads the configuration from [`Config::path`]. A missing file yields the defaults.
+    pub fn load() -> Result<Self, ConfigError> {
+        match Self::path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(ConfigError::Read(path.to_path_buf(), err)),
+        };
+
+        let config: Self =
+            toml::from_str(&content).map_err(|err| ConfigError::Parse(path.to_path_buf(), err))?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let empty = |field, value: &Option<String>| match value {
+            Some(v) if v.trim().is_empty() => Err(ConfigError::Invalid(
+                field,
+                "output name cannot be empty".into(),
+            )),
+            _ => Ok(()),
+        };
+
+        empty("status_bar.main_output", &self.status_bar.main_output)?;
+        if let OsdOutput::Named(output) = &self.osd.output {
+            empty("osd.output", &Some(output.clone()))?;
+        }
+        empty("notifications.output", &self.notifications.output)?;
+
+        if self
+            .brightness
+            .device
+            .as_ref()
+            .is_some_and(|d| d.trim().is_empty() || d.contains('/'))
+        {
+            return Err(ConfigError::Invalid(
+                "brightness.device",
+                "must be the name of a device".into(),
+            ));
+        }
+
+        if self.status_bar.max_title_length == 0 {
+            return Err(ConfigError::Invalid(
+                "status_bar.max_title_length",
+                "must be at least 1 character".into(),
+            ));
+        }
+
+        let layouts = iter::once(("status_bar.layout", &self.status_bar.layout)).chain(
+            self.status_bar
+                .outputs
+                .values()
+                .map(|layout| ("status_bar.outputs", layout)),
+        );
+        for (field, layout) in layouts {
+            if layout.waves.count == Some(0) {
+                return Err(ConfigError::Invalid(
+                    field,
+                    "`waves.count` must be at least 1".into(),
+                ));
+            }
+        }
+
+        let visualizer = &self.status_bar.visualizer;
+        if !(1..=VisualizerConfig::MAX_BARS).contains(&visualizer.bars) {
+            return Err(ConfigError::Invalid(
+                "status_bar.visualizer.bars",
+                format!("must be between 1 and {}", VisualizerConfig::MAX_BARS),
+            ));
+        }
+
+        // Both checks are written for NaN to fail them.
+        if !(0.0..1.0).contains(&visualizer.smoothing) {
+            return Err(ConfigError::Invalid(
+                "status_bar.visualizer.smoothing",
+                "must be at least 0 and below 1".into(),
+            ));
+        }
+
+        if !(visualizer.falloff > 0. && visualizer.falloff <= 1.) {
+            return Err(ConfigError::Invalid(
+                "status_bar.visualizer.falloff",
+                "must be greater than 0 and at most 1".into(),
+            ));
+        }
+
+        if self.notifications.width < NotificationsConfig::MIN_WIDTH {
+            return Err(ConfigError::Invalid(
+                "notifications.width",
+                format!(
+                    "{} is narrower than the minimum of {}px",
+                    self.notifications.width,
+                    NotificationsConfig::MIN_WIDTH
+                ),
+            ));
+        }
+
+        for (field, timeout) in [
+            (
+                "notifications.default_timeout",
+                Some(self.notifications.default_timeout),
+            ),
+            (
+                "notifications.low_timeout",
+                Some(self.notifications.low_timeout),
+            ),
+            (
+                "notifications.critical_timeout",
+                self.notifications.critical_timeout,
+            ),
+        ] {
+            if timeout == Some(0) {
+                return Err(ConfigError::Invalid(
+                    field,
+                    "must be greater than 0 milliseconds".into(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Emits [`Message::ReloadConfig`] every time the configuration file is written, created or
+/// replaced. The parent directory is watched so editors that save through a rename are noticed,
+/// and waited for when it does not exist yet.
+pub fn watch_subscription() -> Subscription<Message> {
+    Subscription::run(|| {
+        stream::channel(1, async |mut output| {
+            let Some(path) = Config::path() else {
+                return;
+            };
+
+            if let Err(err) = watch(&path, &mut output).await {
+                tracing::warn!("Not watching {} for changes: {err}", path.display());
+            }
+        })
+    })
+}
+
+async fn watch(path: &Path, output: &mut Sender<Message>) -> io::Result<()> {
+    let (Some(config_dir), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Ok(());
+    };
+
+    let mut inotify = Inotify::init()?;
+    let readable = Async::new(inotify.as_fd().try_clone_to_owned()?)?;
+    let mut buffer = [0; 4096];
+    let mut was_waiting = false;
+
+    loop {
+        // Until the directory of the file exists, its nearest existing ancestor is watched for it
+        // to be created, one level at a time.
+        let dir = path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.is_dir())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no existing ancestor"))?;
+        let waiting = dir != config_dir;
+
+        let watch = inotify.watches().add(
+            dir,
+            WatchMask::CLOSE_WRITE
+                | WatchMask::MOVED_TO
+                | WatchMask::CREATE
+                | WatchMask::DELETE
+                | WatchMask::DELETE_SELF
+                | WatchMask::MOVE_SELF,
+        )?;
+        if waiting && config_dir.is_dir() {
+            // Created before the watch was in place.
+            let _ = inotify.watches().remove(watch);
+            was_waiting = true;
+            continue;
+        }
+        // The file may have been written along with its directory, before it was watched.
+        if was_waiting && !waiting && path.is_file() {
+            let _ = output.send(Message::ReloadConfig).await;
+        }
+        was_waiting = waiting;
+
+        loop {
+            readable.readable().await?;
+
+            let (mut changed, mut moved) = (false, false);
+            match inotify.read_events(&mut buffer) {
+                Ok(events) => {
+                    // Removed watches still report being ignored.
+                    for event in events.filter(|event| event.wd == watch) {
+                        if event.mask.intersects(
+                            EventMask::IGNORED | EventMask::DELETE_SELF | EventMask::MOVE_SELF,
+                        ) || (waiting && event.mask.contains(EventMask::ISDIR))
+                        {
+                            moved = true;
+                        } else if !waiting && event.name == Some(file_name) {
+                            changed = true;
+                        }
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+
+            if changed {
+                let _ = output.send(Message::ReloadConfig).await;
+            }
+            if moved {
+                // Fails when the watch went away with its directory.
+                let _ = inotify.watches().remove(watch);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use iced::futures::{StreamExt, channel::mpsc};
+    use smol::{Timer, future::FutureExt};
+
+    use super::*;
+
+    /// Runs the watcher on `path` while `change` runs, returning whether it asked for a reload.
+    fn reloads(path: &Path, change: impl Future<Output = ()>) -> bool {
+        let (mut sender, mut receiver) = mpsc::channel(1);
+
+        smol::block_on(
+            async {
+                // Lets the watcher start first.
+                Timer::after(Duration::from_millis(50)).await;
+                change.await;
+                matches!(receiver.next().await, Some(Message::ReloadConfig))
+            }
+            .or(async {
+                let result = watch(path, &mut sender).await;
+                panic!("stopped watching: {result:?}");
+            })
+            .or(async {
+                Timer::after(Duration::from_secs(5)).await;
+                false
+            }),
+        )
+    }
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("waybracelet-{}-{test}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn writes_are_noticed() {
+        let root = temp_dir("config-written");
+        let path = root.join("config.toml");
+
+        assert!(reloads(&path, async { fs::write(&path, "").unwrap() }));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn directories_created_later_are_noticed() {
+        let root = temp_dir("config-created");
+        let path = root.join("xdg/waybracelet/config.toml");
+
+        assert!(reloads(&path, async {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "").unwrap();
+        }));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn directories_created_step_by_step_are_noticed() {
+        let root = temp_dir("config-steps");
+        let path = root.join("xdg/waybracelet/config.toml");
+
+        assert!(reloads(&path, async {
+            fs::create_dir(root.join("xdg")).unwrap();
+            Timer::after(Duration::from_millis(50)).await;
+            fs::create_dir(root.join("xdg/waybracelet")).unwrap();
+            Timer::after(Duration::from_millis(50)).await;
+            fs::write(&path, "").unwrap();
+        }));
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    fmt::{self, Display},
+    io,
+    time::Duration,
+};
+
+use smol::Timer;
+
+use crate::compositor::CompositorError;
+
+/// Failures of the daemon's sources (processes, buses, sockets). None of them is fatal, the
+/// affected feature degrades until its subscription manages to reconnect.
+#[derive(Debug)]
+pub enum Error {
+    Io(&'static str, io::Error),
+    Dbus(zbus::Error),
+    Compositor(CompositorError),
+    /// A source that should run for the whole session went away.
+    Stopped(&'static str),
+    /// A source answered with an error, or with something else than expected.
+    Failed(&'static str, String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(context, err) => write!(f, "{context}: {err}"),
+            Error::Dbus(err) => write!(f, "D-Bus: {err}"),
+            Error::Compositor(err) => write!(f, "{err}"),
+            Error::Stopped(source) => write!(f, "{source} stopped"),
+            Error::Failed(context, reason) => write!(f, "{context}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<zbus::Error> for Error {
+    fn from(err: zbus::Error) -> Self {
+        Error::Dbus(err)
+    }
+}
+
+impl From<CompositorError> for Error {
+    fn from(err: CompositorError) -> Self {
+        Error::Compositor(err)
+    }
+}
+
+/// Doubles the wait between reconnection attempts, up to a minute.
+#[derive(Debug)]
+pub struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    const MIN: Duration = Duration::from_secs(1);
+    const MAX: Duration = Duration::from_secs(60);
+
+    pub fn reset(&mut self) {
+        self.delay = Self::MIN;
+    }
+
+    pub async fn wait(&mut self) {
+        Timer::after(self.delay).await;
+        self.delay = (self.delay * 2).min(Self::MAX);
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self { delay: Self::MIN }
+    }
+}
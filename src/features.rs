@@ -3,7 +3,7 @@ use std::time::Instant;
 use iced::{Element, Subscription, Task, window};
 use iced_layershell::reexport::NewLayerShellSettings;
 
-use crate::{Message, Window};
+use crate::{Message, Window, config::Config};
 
 pub trait Feature: Sized {
     type InnerMessage;
@@ -31,10 +31,39 @@ pub trait Feature: Sized {
         Subscription::none()
     }
 
+    /// Applies a reloaded configuration. Returns `true` when the layer surface has to be
+    /// rebuilt from [`Feature::layer_settings`].
+    fn reconfigure(&mut self, _config: &Config) -> bool {
+        false
+    }
+
+    /// Output the layer surface is pinned to. `None` lets the compositor choose.
+    fn output(&self) -> Option<&str> {
+        None
+    }
+
+    /// Outputs shown on, one layer surface each. Only features opened with
+    /// [`Mirrored`](crate::windows::Mirrored) can be shown on more than one.
+    fn outputs(&self) -> Vec<Option<String>> {
+        vec![self.output().map(str::to_string)]
+    }
+
+    /// Updates the target output from the outputs currently connected. Returns `true` when the
+    /// layer surface has to be rebuilt.
+    fn retarget(&mut self, _outputs: &[String]) -> bool {
+        false
+    }
+
     fn set_now(&mut self, now: Instant);
 }
 
+/// The configured output, as long as it is connected.
+fn connected_output<'a>(configured: Option<&'a str>, outputs: &[String]) -> Option<&'a str> {
+    configured.filter(|c| outputs.iter().any(|o| o == c))
+}
+
 pub mod notifications;
+pub mod osd;
 pub mod power_menu;
 pub mod status_bar;
-pub mod volume_osd;
+pub mod tray_menu;
@@ -10,16 +10,27 @@ use iced::{
     alignment::Horizontal,
     animation::Easing,
     border::{Radius, rounded},
-    time::{milliseconds, seconds},
-    widget::{container, float, keyed::Column, sensor, stack, value},
+    task,
+    time::milliseconds,
+    widget::{container, float, image, keyed::Column, sensor, stack, value},
 };
-use iced_layershell::reexport::{Anchor, KeyboardInteractivity, Layer, NewLayerShellSettings};
-use smol::{Timer, channel::Sender};
-use zbus::zvariant;
+use iced_layershell::reexport::{
+    Anchor, KeyboardInteractivity, Layer, NewLayerShellSettings, OutputOption,
+};
+use smol::{Timer, channel::Sender, process::Command};
+use zbus::zvariant::{self, Value};
 
-use crate::{Message, components::bead_center, features::Feature};
+use crate::{
+    Message,
+    components::bead_center,
+    config::{Config, NotificationsConfig},
+    features::{Feature, connected_output},
+    icons::Icon,
+};
 
+mod activation;
 mod components;
+mod markup;
 pub mod subscriptions;
 
 #[derive(Debug, Clone, Copy)]
@@ -29,32 +40,225 @@ pub enum ExpireTimeout {
     ManualClosing,
 }
 
-impl From<ExpireTimeout> for Option<Duration> {
-    fn from(val: ExpireTimeout) -> Self {
-        match val {
-            ExpireTimeout::ServerDefault => Some(seconds(5)),
+impl ExpireTimeout {
+    pub fn duration(self, server_default: Duration) -> Option<Duration> {
+        match self {
+            ExpireTimeout::ServerDefault => Some(server_default),
             ExpireTimeout::NotificationSpecific(m) => Some(milliseconds(m as u64)),
             ExpireTimeout::ManualClosing => None,
         }
     }
 }
 
+/// How much a notification matters, from its `urgency` hint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Urgency {
+    /// Shown dimmer and for less time.
+    Low,
+    #[default]
+    Normal,
+    /// Shown in the danger color, until closed.
+    Critical,
+}
+
+impl Urgency {
+    /// The level of an `urgency` hint. It is a byte, though some senders use integers.
+    pub fn from_hint(hint: Option<&Value>) -> Self {
+        let level = match hint {
+            Some(Value::U8(level)) => u32::from(*level),
+            Some(Value::U32(level)) => *level,
+            Some(Value::I32(level)) => (*level).max(0) as u32,
+            _ => 1,
+        };
+
+        match level {
+            0 => Urgency::Low,
+            1 => Urgency::Normal,
+            _ => Urgency::Critical,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub key: String,
+    pub label: String,
+    /// Icon named by the key, drawn instead of the label when the sender sets `action-icons`.
+    pub icon: Option<Icon>,
+}
+
+/// Picture shown beside the text of a notification.
+#[derive(Debug, Clone)]
+pub enum NotificationImage {
+    Themed(Icon),
+    Pixmap(image::Handle),
+}
+
 #[derive(Debug, Clone)]
 pub struct Notification {
     pub id: u32,
     pub app_name: String,
     pub app_icon: String,
+    /// `app_icon` looked up, or the icon of the sender's desktop entry.
+    pub icon: Option<Icon>,
+    pub image: Option<NotificationImage>,
     pub summary: String,
     pub body: String,
-    pub actions: Vec<(String, String)>,
+    /// `body` parsed, for its markup to be drawn.
+    pub spans: Vec<markup::Span>,
+    pub actions: Vec<Action>,
     pub hints: HashMap<String, zvariant::OwnedValue>,
     pub expire_timeout: ExpireTimeout,
+    pub urgency: Urgency,
+}
+
+impl Notification {
+    /// Desktop entry of the sender, standing for its app id.
+    pub fn desktop_entry(&self) -> Option<String> {
+        match self.hints.get("desktop-entry").map(|v| &**v) {
+            Some(Value::Str(entry)) if !entry.as_str().is_empty() => {
+                Some(entry.as_str().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether it stays once one of its actions is invoked.
+    pub fn resident(&self) -> bool {
+        matches!(
+            self.hints.get("resident").map(|v| &**v),
+            Some(Value::Bool(true))
+        )
+    }
+
+    /// Whether the action keys are icon names.
+    pub fn action_icons(&self) -> bool {
+        matches!(
+            self.hints.get("action-icons").map(|v| &**v),
+            Some(Value::Bool(true))
+        )
+    }
+
+    /// The `image-path` hint, an icon name or a path. Older senders spell it `image_path`.
+    pub fn image_path(&self) -> Option<String> {
+        ["image-path", "image_path"].into_iter().find_map(|key| {
+            match self.hints.get(key).map(|v| &**v) {
+                Some(Value::Str(path)) if !path.as_str().is_empty() => {
+                    Some(path.as_str().to_string())
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// The raw image of the `image-data` hint, or of the names it had in older versions of the
+    /// specification.
+    pub fn image_data(&self) -> Option<image::Handle> {
+        ["image-data", "image_data", "icon_data"]
+            .into_iter()
+            .find_map(|key| pixmap(self.hints.get(key)?))
+    }
+}
+
+/// Decodes an `(iiibiiay)` image: width, height, row stride, alpha, bits per sample, channels
+/// and the pixels, in RGB or RGBA.
+fn pixmap(value: &Value) -> Option<image::Handle> {
+    let Value::Structure(structure) = value else {
+        return None;
+    };
+    let [
+        Value::I32(width),
+        Value::I32(height),
+        Value::I32(stride),
+        Value::Bool(has_alpha),
+        Value::I32(8),
+        Value::I32(channels),
+        Value::Array(data),
+    ] = structure.fields()
+    else {
+        return None;
+    };
+
+    let (width, height, stride, channels) = (
+        usize::try_from(*width).ok()?,
+        usize::try_from(*height).ok()?,
+        usize::try_from(*stride).ok()?,
+        usize::try_from(*channels).ok()?,
+    );
+    let data = data
+        .iter()
+        .map(|byte| match byte {
+            Value::U8(byte) => Some(*byte),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let rgba = pixmap_rgba(width, height, stride, *has_alpha, channels, &data)?;
+
+    Some(image::Handle::from_rgba(width as u32, height as u32, rgba))
+}
+
+/// Pixels of a pixmap as RGBA, `None` when its layout is inconsistent or its data too short.
+fn pixmap_rgba(
+    width: usize,
+    height: usize,
+    stride: usize,
+    has_alpha: bool,
+    channels: usize,
+    data: &[u8],
+) -> Option<Vec<u8>> {
+    let expected_channels = if has_alpha { 4 } else { 3 };
+    if channels != expected_channels
+        || width == 0
+        || height == 0
+        || stride < width.checked_mul(channels)?
+    {
+        return None;
+    }
+
+    // The last row may stop right after its pixels, without the padding of the others.
+    let rgba = (0..height)
+        .flat_map(|y| (0..width).map(move |x| y * stride + x * channels))
+        .map(|at| {
+            let pixel = data.get(at..at + channels)?;
+            Some([
+                pixel[0],
+                pixel[1],
+                pixel[2],
+                pixel.get(3).copied().unwrap_or(u8::MAX),
+            ])
+        })
+        .collect::<Option<Vec<_>>>()?
+        .concat();
+
+    Some(rgba)
+}
+
+/// Whether a link of a body is safe to hand to `xdg-open`. Senders are untrusted, other schemes
+/// could open local files or run handlers with arguments of their choosing.
+fn is_web_link(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        ["http", "https", "mailto"]
+            .iter()
+            .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+    })
+}
+
+/// Why a notification was closed, as told to its sender by `NotificationClosed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    Expired = 1,
+    Dismissed = 2,
+    /// Closed by a `CloseNotification` call.
+    Closed = 3,
+    Undefined = 4,
 }
 
 #[derive(Debug)]
 pub struct AnimatedNotification {
     notification: Notification,
     animation: Animation<f32>,
+    /// Closes the notification once it expires. Dropping it cancels the timer.
+    expiry: Option<task::Handle>,
 }
 
 impl AnimatedNotification {
@@ -62,6 +266,7 @@ impl AnimatedNotification {
         Self {
             notification,
             animation: Animation::new(1.).quick().easing(Easing::Linear),
+            expiry: None,
         }
     }
 }
@@ -70,26 +275,75 @@ pub struct Notifications {
     notifications: BTreeMap<u32, AnimatedNotification>,
     animation: Animation<f32>,
     dbus_sender: Option<Sender<DbusEvents>>,
+    config: NotificationsConfig,
+    outputs: Vec<String>,
     pub now: Instant,
 }
 
 impl Notifications {
     const PAD: f32 = 24.;
-    const WIDTH: f32 = 500.;
     const ROUND: f32 = Self::PAD * 3.;
 
-    pub fn new(notifications: BTreeMap<u32, AnimatedNotification>, now: Instant) -> Self {
+    pub fn new(
+        notifications: BTreeMap<u32, AnimatedNotification>,
+        config: NotificationsConfig,
+        dbus_sender: Option<Sender<DbusEvents>>,
+        outputs: Vec<String>,
+        now: Instant,
+    ) -> Self {
         Self {
             notifications,
             animation: Animation::new(0.).quick().easing(Easing::Linear),
-            dbus_sender: None,
+            dbus_sender,
+            config,
+            outputs,
             now,
         }
     }
 
-    pub fn add_notification(&mut self, notification: Notification) {
-        self.notifications
-            .insert(notification.id, AnimatedNotification::new(notification));
+    fn width(&self) -> f32 {
+        self.config.width as f32
+    }
+
+    /// Adds a notification, or updates the one it replaces in place: it keeps its position and
+    /// does not slide in again, but expires as late as a new one would.
+    pub fn add_notification(&mut self, notification: Notification) -> Task<Message> {
+        let id = notification.id;
+        match self.notifications.get_mut(&id) {
+            Some(replaced) => {
+                replaced.notification = notification;
+                self.expire(id)
+            }
+            None => {
+                self.notifications
+                    .insert(id, AnimatedNotification::new(notification));
+                Task::none()
+            }
+        }
+    }
+
+    /// (Re)starts the expiry timer of a notification, cancelling the previous one.
+    fn expire(&mut self, id: u32) -> Task<Message> {
+        let Some(an) = self.notifications.get_mut(&id) else {
+            return Task::none();
+        };
+
+        an.expiry = None;
+        let Some(duration) = self
+            .config
+            .timeout(an.notification.urgency, an.notification.expire_timeout)
+        else {
+            return Task::none();
+        };
+
+        let (task, handle) = Task::future(async move {
+            Timer::after(duration).await;
+            Message::Notifications(NotificationsMessage::Close(id, CloseReason::Expired))
+        })
+        .abortable();
+        an.expiry = Some(handle.abort_on_drop());
+
+        task
     }
 
     pub fn start_animation(&mut self, id: u32) {
@@ -102,21 +356,40 @@ impl Notifications {
     pub fn remove_notification(&mut self, id: u32) -> Option<Notification> {
         self.notifications.remove(&id).map(|n| n.notification)
     }
+
+    fn send(&self, event: DbusEvents) {
+        if let Some(sender) = &self.dbus_sender {
+            let _ = sender.try_send(event);
+        }
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> {
+        self.notifications.keys().copied()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum NotificationsMessage {
     New(Notification),
-    Close(u32),
+    Close(u32, CloseReason),
+    /// Dismisses every notification.
+    CloseAll,
+    /// Invokes an action of a notification, closing it unless it is resident.
+    Invoke(u32, String),
+    /// Opens a link of a notification body.
+    OpenLink(String),
     PopUp(u32),
     Remove(u32),
 
     DbusInterfaceReady(Sender<DbusEvents>),
 }
 
+/// Signals to emit on the bus.
+#[derive(Debug)]
 pub enum DbusEvents {
-    ActionInvoked(String, u32),
-    CloseNotification(u32),
+    /// The id, the action key and the desktop entry of the sender, to activate it.
+    ActionInvoked(u32, String, Option<String>),
+    NotificationClosed(u32, CloseReason),
 }
 
 impl Feature for Notifications {
@@ -124,45 +397,79 @@ impl Feature for Notifications {
 
     fn layer_settings(&self) -> iced_layershell::reexport::NewLayerShellSettings {
         NewLayerShellSettings {
-            size: Some((500, 0)),
+            size: Some((self.config.width, 0)),
             layer: Layer::Top,
             margin: Some((16, 0, 32, 0)),
             anchor: Anchor::Right | Anchor::Top | Anchor::Bottom,
             keyboard_interactivity: KeyboardInteractivity::None,
+            output_option: self
+                .output()
+                .map(|o| OutputOption::OutputName(o.to_string()))
+                .unwrap_or(OutputOption::None),
             ..Default::default()
         }
     }
 
     fn update(&mut self, message: NotificationsMessage) -> iced::Task<Message> {
         match message {
-            NotificationsMessage::New(notification) => {
-                self.add_notification(notification);
-                Task::none()
-            }
+            NotificationsMessage::New(notification) => self.add_notification(notification),
             NotificationsMessage::PopUp(id) => {
                 self.start_animation(id);
-                if let Some(duration) = self
+                self.expire(id)
+            }
+            NotificationsMessage::Close(id, reason) => {
+                let Some(notification) = self.notifications.get_mut(&id) else {
+                    return Task::none();
+                };
+                notification.animation.go_mut(1., self.now);
+                // Dropping the timer, a notification closed otherwise does not expire too.
+                notification.expiry = None;
+                self.send(DbusEvents::NotificationClosed(id, reason));
+
+                Task::done(Message::Notifications(NotificationsMessage::Remove(id)))
+            }
+
+            NotificationsMessage::CloseAll => Task::batch(self.ids().map(|id| {
+                Task::done(Message::Notifications(NotificationsMessage::Close(
+                    id,
+                    CloseReason::Dismissed,
+                )))
+            })),
+
+            NotificationsMessage::Invoke(id, key) => {
+                let Some((desktop_entry, resident)) = self
                     .notifications
                     .get(&id)
-                    .as_ref()
-                    .map(|n| n.notification.expire_timeout)
-                    .and_then(Into::into)
-                {
-                    Task::future(async move {
-                        Timer::after(duration).await;
-                        Message::Notifications(NotificationsMessage::Close(id))
-                    })
-                } else {
+                    .map(|n| (n.notification.desktop_entry(), n.notification.resident()))
+                else {
+                    return Task::none();
+                };
+                self.send(DbusEvents::ActionInvoked(id, key, desktop_entry));
+
+                if resident {
                     Task::none()
+                } else {
+                    Task::done(Message::Notifications(NotificationsMessage::Close(
+                        id,
+                        CloseReason::Dismissed,
+                    )))
                 }
             }
-            NotificationsMessage::Close(id) => {
-                if let Some(notification) = self.notifications.get_mut(&id) {
-                    notification.animation.go_mut(1., self.now);
-                }
 
-                Task::done(Message::Notifications(NotificationsMessage::Remove(id)))
+            NotificationsMessage::OpenLink(url) if !is_web_link(&url) => {
+                tracing::warn!("Not opening {url}, only web and mail links are");
+                Task::none()
             }
+            NotificationsMessage::OpenLink(url) => Task::future(async move {
+                match Command::new("xdg-open").arg(&url).status().await {
+                    Ok(status) if !status.success() => {
+                        tracing::warn!("xdg-open {url} exited with {status}");
+                    }
+                    Ok(_) => {}
+                    Err(err) => tracing::warn!("Could not run xdg-open: {err}"),
+                }
+            })
+            .discard(),
 
             NotificationsMessage::Remove(id) => {
                 self.remove_notification(id);
@@ -185,7 +492,7 @@ impl Feature for Notifications {
         let a = self
             .notifications
             .iter()
-            .map(|(id, n)| (*id, components::notification(self.now, n)));
+            .map(|(id, n)| (*id, components::notification(self.now, self.width(), n)));
 
         float(stack![
             container(Column::with_children(a).spacing(Self::PAD))
@@ -205,7 +512,7 @@ impl Feature for Notifications {
                         .right(0.)
                         .left(Notifications::PAD + 56. / 2. - 8.)
                 )
-                .width(Notifications::WIDTH - 56. / 2.)
+                .width(self.width() - 56. / 2.)
                 .align_x(Horizontal::Right)
                 .height(Shrink),
             float(
@@ -235,7 +542,100 @@ impl Feature for Notifications {
                 .any(|(_, n)| n.animation.is_animating(self.now))
     }
 
+    fn reconfigure(&mut self, config: &Config) -> bool {
+        let before = (self.config.width, self.output().map(str::to_string));
+        self.config = config.notifications.clone();
+        before != (self.config.width, self.output().map(str::to_string))
+    }
+
+    fn output(&self) -> Option<&str> {
+        connected_output(self.config.output.as_deref(), &self.outputs)
+    }
+
+    fn retarget(&mut self, outputs: &[String]) -> bool {
+        let before = self.output().map(str::to_string);
+        self.outputs = outputs.to_vec();
+        before.as_deref() != self.output()
+    }
+
     fn set_now(&mut self, now: Instant) {
         self.now = now;
     }
 }
+
+impl Drop for Notifications {
+    /// Notifications still shown when the window goes away are closed for no reason their
+    /// senders would know of.
+    fn drop(&mut self) {
+        for id in self.ids() {
+            self.send(DbusEvents::NotificationClosed(id, CloseReason::Undefined));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_web_links_open() {
+        assert!(is_web_link("https://example.org/a?b=c:d"));
+        assert!(is_web_link("HTTP://example.org"));
+        assert!(is_web_link("mailto:someone@example.org"));
+
+        assert!(!is_web_link("file:///etc/passwd"));
+        assert!(!is_web_link("/home/someone/script.sh"));
+        assert!(!is_web_link("javascript:alert(1)"));
+        assert!(!is_web_link("smb://host/share"));
+        assert!(!is_web_link("example.org/http:"));
+        assert!(!is_web_link(""));
+    }
+
+    #[test]
+    fn pixmap_rows_skip_their_padding() {
+        #[rustfmt::skip]
+        let data = [
+            1, 2, 3, 4, 5, 6, 0, 0,
+            7, 8, 9, 10, 11, 12, 0, 0,
+        ];
+
+        assert_eq!(
+            pixmap_rgba(2, 2, 8, false, 3, &data),
+            Some(vec![
+                1, 2, 3, 255, 4, 5, 6, 255, 7, 8, 9, 255, 10, 11, 12, 255
+            ])
+        );
+    }
+
+    #[test]
+    fn pixmap_alpha_is_kept() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        assert_eq!(pixmap_rgba(2, 1, 8, true, 4, &data), Some(data.to_vec()));
+    }
+
+    #[test]
+    fn pixmap_alpha_must_match_the_channels() {
+        assert_eq!(pixmap_rgba(1, 1, 4, false, 4, &[1, 2, 3, 4]), None);
+        assert_eq!(pixmap_rgba(1, 1, 3, true, 3, &[1, 2, 3]), None);
+        assert_eq!(pixmap_rgba(1, 1, 2, false, 2, &[1, 2]), None);
+    }
+
+    #[test]
+    fn pixmap_last_row_may_be_unpadded() {
+        let data = [1, 2, 3, 0, 4, 5, 6];
+
+        assert_eq!(
+            pixmap_rgba(1, 2, 4, false, 3, &data),
+            Some(vec![1, 2, 3, 255, 4, 5, 6, 255])
+        );
+    }
+
+    #[test]
+    fn pixmap_too_short_is_none() {
+        assert_eq!(pixmap_rgba(1, 2, 4, false, 3, &[1, 2, 3, 0, 4, 5]), None);
+        assert_eq!(pixmap_rgba(2, 2, 6, false, 3, &[0; 11]), None);
+        assert_eq!(pixmap_rgba(2, 1, 5, false, 3, &[0; 6]), None);
+        assert_eq!(pixmap_rgba(0, 1, 0, false, 3, &[]), None);
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use wayland_client::{
+    Connection, Dispatch, QueueHandle,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::wl_registry::{self, WlRegistry},
+};
+use wayland_protocols::xdg::activation::v1::client::{
+    xdg_activation_token_v1::{self, XdgActivationTokenV1},
+    xdg_activation_v1::{self, XdgActivationV1},
+};
+
+use crate::error::Error;
+
+#[derive(Default)]
+struct State {
+    token: Option<String>,
+}
+
+/// Asks the compositor for an `xdg-activation-v1` token, for the application whose action was
+/// invoked to raise its window. No input serial backs it, so the compositor may still keep the
+/// focus where it is.
+pub fn token(app_id: Option<&str>) -> Result<String, Error> {
+    let connection = Connection::connect_to_env().map_err(failed)?;
+    let (globals, mut queue) = registry_queue_init::<State>(&connection).map_err(failed)?;
+    let qh = queue.handle();
+
+    let activation = globals
+        .bind::<XdgActivationV1, _, _>(&qh, 1..=1, ())
+        .map_err(failed)?;
+    let request = activation.get_activation_token(&qh, ());
+    if let Some(app_id) = app_id {
+        request.set_app_id(app_id.to_string());
+    }
+    request.commit();
+
+    let mut state = State::default();
+    let token = loop {
+        queue.blocking_dispatch(&mut state).map_err(failed)?;
+        if let Some(token) = state.token.take() {
+            break token;
+        }
+    };
+    request.destroy();
+    activation.destroy();
+
+    Ok(token)
+}
+
+fn failed(err: impl Display) -> Error {
+    Error::Failed("could not get an activation token", err.to_string())
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<XdgActivationV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &XdgActivationV1,
+        _: xdg_activation_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<XdgActivationTokenV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &XdgActivationTokenV1,
+        event: xdg_activation_token_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_activation_token_v1::Event::Done { token } = event {
+            state.token = Some(token);
+        }
+    }
+}
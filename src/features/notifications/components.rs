@@ -1,63 +1,159 @@
 use std::time::Instant;
 
 use iced::{
-    Element,
+    Alignment::Center,
+    Element, Font,
     Length::{Fill, Shrink},
     Padding, Shadow, Vector,
     border::{Radius, rounded},
+    font::{Style, Weight},
     time::milliseconds,
-    widget::{Row, button, column, container, float, sensor, value},
+    widget::{
+        Row, button, column, container, float, image, mouse_area, rich_text, sensor, span, value,
+    },
 };
 
 use crate::{
     Message,
-    features::notifications::{AnimatedNotification, Notifications, NotificationsMessage},
+    features::notifications::{
+        AnimatedNotification, CloseReason, NotificationImage, Notifications, NotificationsMessage,
+        Urgency, markup,
+    },
 };
 
 pub fn notification<'a>(
     now: Instant,
+    width: f32,
     notification: &'a AnimatedNotification,
 ) -> Element<'a, Message> {
     let n_notification = notification.notification.clone();
+    let id = n_notification.id;
+    let urgency = n_notification.urgency;
 
+    // The default action is invoked by clicking the notification, not by a button of its own.
     let actions = Row::from_iter(
         n_notification
             .actions
             .iter()
-            .map(|(key, text)| button(value(text).width(Fill).center()).into()),
+            .filter(|action| action.key != "default")
+            .map(|action| {
+                let content = match &action.icon {
+                    Some(icon) => container(icon.view(20)).center_x(Fill),
+                    None => container(value(&action.label).width(Fill).center()),
+                };
+
+                button(content)
+                    .on_press(Message::Notifications(NotificationsMessage::Invoke(
+                        id,
+                        action.key.clone(),
+                    )))
+                    .into()
+            }),
     );
+    let on_click = if n_notification
+        .actions
+        .iter()
+        .any(|action| action.key == "default")
+    {
+        NotificationsMessage::Invoke(id, "default".to_string())
+    } else {
+        NotificationsMessage::Close(id, CloseReason::Dismissed)
+    };
+
+    let header = Row::with_children(
+        n_notification
+            .icon
+            .as_ref()
+            .map(|icon| icon.view(16))
+            .into_iter()
+            .chain([value(n_notification.app_name).into()]),
+    )
+    .spacing(8)
+    .align_y(Center);
+    let picture = n_notification.image.as_ref().map(|picture| match picture {
+        NotificationImage::Themed(icon) => icon.view(64),
+        NotificationImage::Pixmap(handle) => image(handle.clone()).width(64).height(64).into(),
+    });
+    let text = column![
+        header,
+        value(n_notification.summary),
+        body(&n_notification.spans),
+        actions
+    ]
+    .width(Fill);
 
     sensor(
         float(
-            container(column![
-                value(n_notification.app_name),
-                value(n_notification.summary),
-                value(n_notification.body),
-                actions
-            ])
-            .padding(Padding::new(
-                (Notifications::ROUND - Notifications::PAD) * 2. / 3.,
-            ))
-            .style(|theme: &iced::Theme| container::Style {
-                text_color: Some(theme.palette().text),
-                background: Some(theme.palette().background.into()),
-                border: rounded(Radius::default().left(Notifications::ROUND - Notifications::PAD)),
-                shadow: Shadow::default(),
-                snap: true,
-            })
-            .height(Shrink)
-            .width(Fill),
+            mouse_area(
+                container(
+                    Row::with_children(picture.into_iter().chain([text.into()]))
+                        .spacing(Notifications::PAD / 2.),
+                )
+                .padding(Padding::new(
+                    (Notifications::ROUND - Notifications::PAD) * 2. / 3.,
+                ))
+                .style(move |theme: &iced::Theme| {
+                    let palette = theme.palette();
+                    let border =
+                        rounded(Radius::default().left(Notifications::ROUND - Notifications::PAD));
+
+                    container::Style {
+                        text_color: Some(match urgency {
+                            Urgency::Low => palette.text.scale_alpha(0.6),
+                            Urgency::Normal => palette.text,
+                            Urgency::Critical => palette.danger,
+                        }),
+                        background: Some(palette.background.into()),
+                        border: match urgency {
+                            Urgency::Critical => border.width(4.).color(palette.danger),
+                            Urgency::Low | Urgency::Normal => border,
+                        },
+                        shadow: Shadow::default(),
+                        snap: true,
+                    }
+                })
+                .height(Shrink)
+                .width(Fill),
+            )
+            .on_press(Message::Notifications(on_click)),
         )
         .translate(move |_, _| {
             Vector::new(
                 notification
                     .animation
-                    .interpolate_with(|f| f * 500., now + milliseconds(100)),
+                    .interpolate_with(|f| f * width, now + milliseconds(100)),
                 0.,
             )
         }),
     )
-    .key(n_notification.id)
-    .on_show(move |s| Message::Notifications(NotificationsMessage::PopUp(n_notification.id)))
+    .key(id)
+    .on_show(move |_| Message::Notifications(NotificationsMessage::PopUp(id)))
     .into()
 }
+
+/// The body with its markup, links opening on click.
+fn body<'a>(spans: &[markup::Span]) -> Element<'a, Message> {
+    let spans = spans
+        .iter()
+        .map(|s| {
+            let font = Font {
+                weight: if s.bold { Weight::Bold } else { Weight::Normal },
+                style: if s.italic {
+                    Style::Italic
+                } else {
+                    Style::Normal
+                },
+                ..Font::DEFAULT
+            };
+
+            span(s.text.clone())
+                .font(font)
+                .underline(s.underline || s.link.is_some())
+                .link_maybe(s.link.clone())
+        })
+        .collect::<Vec<_>>();
+
+    rich_text(spans)
+        .on_link_click(|url| Message::Notifications(NotificationsMessage::OpenLink(url)))
+        .into()
+}
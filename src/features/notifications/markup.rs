@@ -0,0 +1,465 @@
+/// A run of the body sharing the same styling.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Target of the `<a>` around the text.
+    pub link: Option<String>,
+}
+
+impl Span {
+    fn same_style(&self, other: &Span) -> bool {
+        (self.bold, self.italic, self.underline, &self.link)
+            == (other.bold, other.italic, other.underline, &other.link)
+    }
+}
+
+/// Tags the run being read is inside of, counted since they may nest.
+#[derive(Debug, Default)]
+struct State {
+    bold: usize,
+    italic: usize,
+    underline: usize,
+    links: Vec<Option<String>>,
+}
+
+impl State {
+    fn push(&self, spans: &mut Vec<Span>, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        let span = Span {
+            text,
+            bold: self.bold > 0,
+            italic: self.italic > 0,
+            underline: self.underline > 0,
+            link: self.links.iter().rev().find_map(Clone::clone),
+        };
+        match spans.last_mut() {
+            Some(last) if last.same_style(&span) => last.text.push_str(&span.text),
+            _ => spans.push(span),
+        }
+    }
+}
+
+/// Parses the markup of a notification body: `<b>`, `<i>`, `<u>`, `<a href>` and `<img alt>`
+/// from the specification, plus `<br>`, and the XML entities. Other tags are dropped, a `<` that
+/// does not open a tag is kept as is.
+pub fn parse(markup: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut state = State::default();
+    let mut rest = markup;
+
+    while let Some(first) = rest.chars().next() {
+        if first == '<'
+            && let Some((tag, after)) = Tag::parse(&rest[1..])
+        {
+            tag.apply(&mut state, &mut spans);
+            rest = after;
+            continue;
+        }
+
+        let end = rest[first.len_utf8()..]
+            .find('<')
+            .map_or(rest.len(), |at| at + first.len_utf8());
+        state.push(&mut spans, decode(&rest[..end]));
+        rest = &rest[end..];
+    }
+
+    spans
+}
+
+struct Tag<'a> {
+    name: String,
+    closing: bool,
+    attributes: &'a str,
+}
+
+impl<'a> Tag<'a> {
+    /// Reads a tag from right after its `<`, returning it and what follows its `>`.
+    fn parse(markup: &'a str) -> Option<(Self, &'a str)> {
+        let (closing, inner) = match markup.strip_prefix('/') {
+            Some(inner) => (true, inner),
+            None => (false, markup),
+        };
+        if !inner.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        // A `>` inside a quoted attribute does not close the tag.
+        let mut quote = None;
+        let end = inner.char_indices().find_map(|(at, c)| {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(open), c) if c == open => quote = None,
+                (None, '>') => return Some(at),
+                _ => {}
+            }
+            None
+        })?;
+
+        let inner_tag = &inner[..end];
+        let name_end = inner_tag
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(inner_tag.len());
+        let attributes = &inner_tag[name_end..];
+        if !(attributes.is_empty()
+            || attributes.starts_with(|c: char| c.is_whitespace() || c == '/'))
+        {
+            return None;
+        }
+
+        Some((
+            Tag {
+                name: inner_tag[..name_end].to_ascii_lowercase(),
+                closing,
+                attributes,
+            },
+            &inner[end + 1..],
+        ))
+    }
+
+    /// Value of an attribute, quoted or not.
+    fn attribute(&self, name: &str) -> Option<String> {
+        let mut rest = self
+            .attributes
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+
+        while !rest.is_empty() {
+            let key_end = rest
+                .find(|c: char| c == '=' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            rest = rest[key_end..].trim_start();
+
+            let value = match rest.strip_prefix('=') {
+                Some(after) => {
+                    let after = after.trim_start();
+                    let (value, remaining) = match after.chars().next() {
+                        Some(quote @ ('"' | '\'')) => {
+                            let inner = &after[1..];
+                            let end = inner.find(quote).unwrap_or(inner.len());
+                            (&inner[..end], inner.get(end + 1..).unwrap_or_default())
+                        }
+                        _ => {
+                            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                            (&after[..end], &after[end..])
+                        }
+                    };
+                    rest = remaining;
+                    Some(value)
+                }
+                None => None,
+            };
+
+            if key.eq_ignore_ascii_case(name) {
+                return value.map(decode);
+            }
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        }
+
+        None
+    }
+
+    /// Whether the tag is written as `<tag/>`, opening and closing at once.
+    fn self_closing(&self) -> bool {
+        self.attributes.trim_end().ends_with('/')
+    }
+
+    fn apply(&self, state: &mut State, spans: &mut Vec<Span>) {
+        let count = match self.name.as_str() {
+            // Styles nothing.
+            "b" | "i" | "u" if self.self_closing() => return,
+            "b" => &mut state.bold,
+            "i" => &mut state.italic,
+            "u" => &mut state.underline,
+            "a" if self.closing => {
+                state.links.pop();
+                return;
+            }
+            "a" => {
+                let href = self.attribute("href").filter(|href| !href.is_empty());
+                state.links.push(href);
+                return;
+            }
+            // Images are not drawn inline, their description stands for them.
+            "img" if !self.closing => {
+                if let Some(alt) = self.attribute("alt") {
+                    state.push(spans, alt);
+                }
+                return;
+            }
+            "br" => {
+                state.push(spans, "\n".to_string());
+                return;
+            }
+            _ => return,
+        };
+
+        *count = if self.closing {
+            count.saturating_sub(1)
+        } else {
+            *count + 1
+        };
+    }
+}
+
+/// Replaces the XML entities and character references. An `&` starting none of them is kept.
+fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(at) = rest.find('&') {
+        decoded.push_str(&rest[..at]);
+        rest = &rest[at..];
+
+        match rest
+            .find(';')
+            .and_then(|end| Some((entity(&rest[1..end])?, end)))
+        {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let code = name.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) if !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    u32::from_str_radix(hex, 16).ok()?
+                }
+                None if !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()) => {
+                    code.parse().ok()?
+                }
+                _ => return None,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(spans: &[Span]) -> String {
+        spans.iter().map(|s| s.text.as_str()).collect()
+    }
+
+    fn plain(text: &str) -> Span {
+        Span {
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn styles_nest() {
+        let spans = parse("a<b>b<i>c</i></b><u>d</u>");
+
+        assert_eq!(
+            spans,
+            [
+                plain("a"),
+                Span {
+                    bold: true,
+                    ..plain("b")
+                },
+                Span {
+                    bold: true,
+                    italic: true,
+                    ..plain("c")
+                },
+                Span {
+                    underline: true,
+                    ..plain("d")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn links_keep_their_target() {
+        let spans = parse(r#"see <a href="https://example.org/?a=1&amp;b=2">this</a>."#);
+
+        assert_eq!(
+            spans,
+            [
+                plain("see "),
+                Span {
+                    link: Some("https://example.org/?a=1&b=2".to_string()),
+                    ..plain("this")
+                },
+                plain("."),
+            ]
+        );
+    }
+
+    #[test]
+    fn link_targets_may_hold_a_closing_bracket() {
+        let spans = parse("<a href='x>y'>z</a>");
+
+        assert_eq!(spans[0].link.as_deref(), Some("x>y"));
+        assert_eq!(text(&spans), "z");
+    }
+
+    #[test]
+    fn images_stand_as_their_description() {
+        assert_eq!(text(&parse(r#"<img src="a.png" alt="a cat"/>!"#)), "a cat!");
+        assert_eq!(text(&parse("<img src=a.png>")), "");
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        assert_eq!(
+            text(&parse("&lt;b&gt; &amp;amp; &#65;&#x42; &quot;&apos;")),
+            "<b> &amp; AB \"'"
+        );
+    }
+
+    #[test]
+    fn unknown_entities_stay() {
+        assert_eq!(
+            text(&parse("R&D &foo; &#; &#x; &#+5; &#xD800; &")),
+            "R&D &foo; &#; &#x; &#+5; &#xD800; &"
+        );
+    }
+
+    #[test]
+    fn stray_brackets_stay() {
+        assert_eq!(text(&parse("1 < 2 and 3 > 2")), "1 < 2 and 3 > 2");
+        assert_eq!(text(&parse("<<b>x")), "<x");
+        assert_eq!(text(&parse("<b")), "<b");
+        assert_eq!(text(&parse("a <")), "a <");
+    }
+
+    #[test]
+    fn unknown_tags_are_dropped() {
+        assert_eq!(parse("<span>x</span><br/>y"), [plain("x\ny")]);
+    }
+
+    #[test]
+    fn self_closing_styles_do_nothing() {
+        assert_eq!(parse("<b/>x<i />y<u/>z"), [plain("xyz")]);
+        assert_eq!(
+            parse("<b>x<b/>y</b>z"),
+            [
+                Span {
+                    bold: true,
+                    ..plain("xy")
+                },
+                plain("z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unbalanced_tags_do_not_leak() {
+        assert_eq!(parse("</b>x</a></i>"), [plain("x")]);
+        assert_eq!(
+            parse("<b>x"),
+            [Span {
+                bold: true,
+                ..plain("x")
+            }]
+        );
+        assert_eq!(text(&parse("<a href=x>y</a></a>z")), "yz");
+    }
+
+    /// Deterministic xorshift, for the fuzzing below to be reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as usize
+        }
+    }
+
+    fn random_markup(rng: &mut Rng) -> String {
+        const FRAGMENTS: [&str; 24] = [
+            "<",
+            ">",
+            "/",
+            "&",
+            ";",
+            "#",
+            "x",
+            "b",
+            "i",
+            "u",
+            "a",
+            "<b>",
+            "</b>",
+            "<i>",
+            "</u>",
+            "<a href=\"",
+            "\"",
+            "'",
+            "</a>",
+            "<img alt='",
+            "&amp;",
+            "&#x1F600;",
+            "é",
+            "𝄞",
+        ];
+
+        (0..rng.next() % 32)
+            .map(|_| FRAGMENTS[rng.next() % FRAGMENTS.len()])
+            .collect()
+    }
+
+    #[test]
+    fn malformed_markup_never_panics() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..20_000 {
+            let markup = random_markup(&mut rng);
+            let spans = parse(&markup);
+
+            assert!(spans.iter().all(|s| !s.text.is_empty()), "{markup:?}");
+            assert!(
+                spans.windows(2).all(|w| !w[0].same_style(&w[1])),
+                "{markup:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn escaped_text_comes_back_unchanged() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+        for _ in 0..20_000 {
+            let original = random_markup(&mut rng);
+            let escaped = original
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+
+            assert_eq!(text(&parse(&escaped)), original);
+        }
+    }
+}
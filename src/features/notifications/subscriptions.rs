@@ -1,15 +1,25 @@
 use std::collections::HashMap;
 
-use iced::futures::SinkExt;
+use iced::futures::{SinkExt, future::Either};
 use iced::{Subscription, stream};
 use smol::channel::{Sender, unbounded};
 use zbus::conn::Builder;
 use zbus::object_server::SignalEmitter;
 use zbus::{interface, zvariant};
 
-use crate::{FeatureSelector, Message};
+use crate::{
+    FeatureSelector, Message,
+    error::{Backoff, Error},
+    features::osd::{OsdMessage, OsdRequest},
+    icons,
+};
 
-use super::{ExpireTimeout, Notification, NotificationsMessage};
+use super::{
+    Action, CloseReason, DbusEvents, ExpireTimeout, Notification, NotificationImage,
+    NotificationsMessage, Urgency, activation, markup,
+};
+
+const PATH: &str = "/org/freedesktop/Notifications";
 
 #[derive(Debug)]
 struct NotificationsManager {
@@ -48,22 +58,32 @@ impl NotificationsManager {
             id,
             app_name,
             app_icon,
+            icon: None,
+            image: None,
             summary,
+            spans: markup::parse(&body),
             body,
             actions: actions
                 .chunks(2)
                 .filter_map(|a| {
-                    if let [key, text] = a {
-                        Some((key.to_string(), text.to_string()))
+                    if let [key, label] = a {
+                        Some(Action {
+                            key: key.to_string(),
+                            label: label.to_string(),
+                            icon: None,
+                        })
                     } else {
                         None
                     }
                 })
                 .collect(),
+            urgency: Urgency::from_hint(hints.get("urgency").map(|v| &**v)),
             hints,
             expire_timeout,
         };
 
+        let notification = smol::unblock(move || with_images(notification)).await;
+
         let _ = self
             .sender
             .send(NotificationsMessage::New(notification))
@@ -88,7 +108,10 @@ impl NotificationsManager {
     }
 
     async fn close_notification(&mut self, id: u32) {
-        let _ = self.sender.send(NotificationsMessage::Close(id)).await;
+        let _ = self
+            .sender
+            .send(NotificationsMessage::Close(id, CloseReason::Closed))
+            .await;
     }
 
     fn get_server_information(&self) -> (String, String, String, String) {
@@ -104,7 +127,7 @@ impl NotificationsManager {
     async fn notification_closed(
         ctx: &SignalEmitter<'_>,
         id: u32,
-        reason: String,
+        reason: u32,
     ) -> Result<(), zbus::Error>;
 
     #[zbus(signal)]
@@ -122,51 +145,126 @@ impl NotificationsManager {
     ) -> Result<(), zbus::Error>;
 }
 
+/// Looks up the icons of a notification and decodes its image, which may read from the disk.
+fn with_images(mut notification: Notification) -> Notification {
+    notification.icon = Some(notification.app_icon.as_str())
+        .filter(|icon| !icon.is_empty())
+        .and_then(icons::lookup)
+        .or_else(|| icons::for_app(&notification.desktop_entry()?));
+    notification.image = notification
+        .image_data()
+        .map(NotificationImage::Pixmap)
+        .or_else(|| {
+            let path = notification.image_path()?;
+            icons::lookup(&path).map(NotificationImage::Themed)
+        });
+
+    if notification.action_icons() {
+        for action in &mut notification.actions {
+            action.icon = icons::lookup(&action.key);
+        }
+    }
+
+    notification
+}
+
+async fn serve(sender: Sender<NotificationsMessage>) -> Result<zbus::Connection, Error> {
+    let iface = NotificationsManager {
+        sender,
+        current_id: 0,
+    };
+
+    let connection = Builder::session()?
+        .name("org.freedesktop.Notifications")?
+        .serve_at(PATH, iface)?
+        .build()
+        .await?;
+
+    Ok(connection)
+}
+
+/// Emits a signal asked for by the notifications window.
+async fn emit(connection: &zbus::Connection, event: DbusEvents) -> Result<(), Error> {
+    let emitter = SignalEmitter::new(connection, PATH)?;
+
+    match event {
+        DbusEvents::ActionInvoked(id, key, desktop_entry) => {
+            // The token comes first, for the sender to have it when handling the action.
+            match smol::unblock(move || activation::token(desktop_entry.as_deref())).await {
+                Ok(token) => NotificationsManager::activation_token(&emitter, id, token).await?,
+                Err(err) => tracing::debug!("No activation token for notification {id}: {err}"),
+            }
+            NotificationsManager::action_invoked(&emitter, id, key).await?;
+        }
+        DbusEvents::NotificationClosed(id, reason) => {
+            NotificationsManager::notification_closed(&emitter, id, reason as u32).await?;
+        }
+    }
+
+    Ok(())
+}
+
 pub fn notifications_subscription() -> Subscription<Message> {
     Subscription::run(|| {
         stream::channel(1, async |mut output| {
-            let (tx, mut rx) = unbounded();
+            let (tx, rx) = unbounded();
 
-            let iface = NotificationsManager {
-                sender: tx,
-                current_id: 0,
+            // Another notification daemon may own the name, keep trying until it goes away.
+            let mut backoff = Backoff::default();
+            let connection = loop {
+                match serve(tx.clone()).await {
+                    Ok(connection) => break connection,
+                    Err(err) => {
+                        tracing::warn!("Could not serve org.freedesktop.Notifications: {err}");
+                        backoff.wait().await;
+                    }
+                }
             };
-            let connection = Builder::session()
-                .unwrap()
-                .name("org.freedesktop.Notifications")
-                .unwrap()
-                .serve_at("/org/freedesktop/Notifications", iface)
-                .unwrap()
-                .build()
-                .await
-                .unwrap();
-
-            connection
-                .request_name("org.freedesktop.Notifications")
-                .await
-                .unwrap();
-
-            let (tx_id, mut rx_id) = unbounded();
+
+            let (tx_signals, rx_signals) = unbounded();
 
             let _ = output
                 .send(Message::Notifications(
-                    NotificationsMessage::DbusInterfaceReady(tx_id),
+                    NotificationsMessage::DbusInterfaceReady(tx_signals),
                 ))
                 .await;
 
             loop {
-                if let Ok(event) = rx.recv().await {
-                    let _ = output
-                        .send(Message::Open(FeatureSelector::Notifications))
-                        .await;
-                    let _ = output.send(Message::Notifications(event)).await;
+                let call = async { rx.recv().await.map(Either::Left) };
+                let signal = async { rx_signals.recv().await.map(Either::Right) };
+
+                match smol::future::or(call, signal).await {
+                    Ok(Either::Left(event)) => {
+                        if let NotificationsMessage::New(notification) = &event {
+                            // Synchronous notifications are feedback for a change, shown on
+                            // the OSD. They are not kept, so they are closed right away for their
+                            // sender to know.
+                            if let Some(request) = OsdRequest::from_notification(notification) {
+                                let _ = output.send(Message::Osd(OsdMessage::Show(request))).await;
+
+                                let closed = DbusEvents::NotificationClosed(
+                                    notification.id,
+                                    CloseReason::Undefined,
+                                );
+                                if let Err(err) = emit(&connection, closed).await {
+                                    tracing::warn!("Could not emit a notifications signal: {err}");
+                                }
+                                continue;
+                            }
+
+                            let _ = output
+                                .send(Message::Open(FeatureSelector::Notifications))
+                                .await;
+                        }
+                        let _ = output.send(Message::Notifications(event)).await;
+                    }
+                    Ok(Either::Right(signal)) => {
+                        if let Err(err) = emit(&connection, signal).await {
+                            tracing::warn!("Could not emit a notifications signal: {err}");
+                        }
+                    }
+                    Err(_) => break,
                 }
-                // select! {
-                //     Some(event) = rx_id.recv() => {
-                //
-                //     },
-                //     else => continue
-                // };
             }
         })
     })
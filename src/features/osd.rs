@@ -0,0 +1,598 @@
+use std::iter;
+
+use iced::{
+    Animation, Element,
+    Length::Fill,
+    Point, Subscription, Task,
+    mouse::ScrollDelta,
+    time::{Instant, milliseconds, seconds},
+    widget::{Stack, Text, container, mouse_area},
+};
+use iced_layershell::reexport::{
+    Anchor, KeyboardInteractivity, Layer, NewLayerShellSettings, OutputOption,
+};
+use lucide_icons::iced::{
+    icon_gauge, icon_sun, icon_toggle_left, icon_toggle_right, icon_volume_2, icon_volume_x,
+};
+use smol::Timer;
+use zbus::zvariant::Value;
+
+use crate::{
+    Message,
+    brightness::{self, BrightnessChange},
+    compositor::Backend,
+    config::{BrightnessConfig, Config, OsdConfig, OsdOutput},
+    features::{Feature, connected_output, notifications::Notification},
+    icons::{self, Icon},
+    sink::{self, SinkVolume, VOLUME_STEP},
+};
+
+mod components;
+mod subscriptions;
+
+/// Size of the layer surface.
+const SIZE: (u32, u32) = (500, 300);
+/// Time the OSD stays up after the last change.
+const SHOWN_FOR: u64 = 2;
+/// Time the OSD takes to fade out.
+const FADE_OUT: u64 = 250;
+
+/// What the OSD is asked to show, through IPC or a notification. Icons are names from the icon
+/// theme or paths.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OsdRequest {
+    /// The default sink's volume, `1` being 100%.
+    Volume(f32),
+    Brightness(f32),
+    /// Any other level between `0` and `1`.
+    Level(f32, Option<String>),
+    /// An on/off state, such as caps lock or the microphone's mute.
+    Toggle {
+        on: bool,
+        label: String,
+        icon: Option<String>,
+    },
+    /// A short text, such as the keyboard layout switched to.
+    Text(String, Option<String>),
+}
+
+impl OsdRequest {
+    /// What a notification carrying the `x-canonical-private-synchronous` hint asks for in place
+    /// of a popup: its `value` hint as a level, or else its summary as text.
+    pub fn from_notification(notification: &Notification) -> Option<Self> {
+        notification.hints.get("x-canonical-private-synchronous")?;
+
+        let icon = Some(notification.app_icon.clone()).filter(|i| !i.is_empty());
+        let value = notification
+            .hints
+            .get("value")
+            .and_then(|value| match &**value {
+                Value::I32(v) => Some(*v as f32),
+                Value::U32(v) => Some(*v as f32),
+                Value::I64(v) => Some(*v as f32),
+                Value::U8(v) => Some(f32::from(*v)),
+                _ => None,
+            });
+
+        Some(match value {
+            Some(value) => OsdRequest::Level(value / 100., icon),
+            None => OsdRequest::Text(notification.summary.clone(), icon),
+        })
+    }
+}
+
+/// An icon of the OSD, drawn on a bead above the start of the bar.
+#[derive(Debug, Clone)]
+pub enum Symbol {
+    Lucide(fn() -> Text<'static>),
+    Themed(Icon),
+}
+
+impl Symbol {
+    /// The themed icon named by a request, or `fallback` when it has none or it is not found.
+    fn lookup(name: Option<String>, fallback: fn() -> Text<'static>) -> Self {
+        name.and_then(|name| icons::lookup(&name))
+            .map(Symbol::Themed)
+            .unwrap_or(Symbol::Lucide(fallback))
+    }
+}
+
+/// A level the OSD can change, when it is interactive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjustable {
+    Volume,
+    Brightness,
+}
+
+/// What the OSD currently shows.
+#[derive(Debug, Clone)]
+pub enum OsdContent {
+    Level {
+        icon: Symbol,
+        level: f32,
+        muted: bool,
+        adjusts: Option<Adjustable>,
+    },
+    Toggle {
+        icon: Symbol,
+        label: String,
+        on: bool,
+    },
+    Text {
+        icon: Option<Symbol>,
+        text: String,
+    },
+}
+
+impl OsdContent {
+    fn volume(sink: SinkVolume) -> Self {
+        OsdContent::Level {
+            icon: Symbol::Lucide(if sink.muted {
+                icon_volume_x
+            } else {
+                icon_volume_2
+            }),
+            level: sink.volume.clamp(0., 1.),
+            muted: sink.muted,
+            adjusts: Some(Adjustable::Volume),
+        }
+    }
+
+    fn brightness(level: f32) -> Self {
+        OsdContent::Level {
+            icon: Symbol::Lucide(icon_sun),
+            level: level.clamp(0., 1.),
+            muted: false,
+            adjusts: Some(Adjustable::Brightness),
+        }
+    }
+
+    /// Content of a request, with the icon it names looked up in the icon theme.
+    fn lookup(request: OsdRequest) -> Self {
+        match request {
+            OsdRequest::Volume(volume) => OsdContent::volume(SinkVolume {
+                volume,
+                muted: false,
+            }),
+            OsdRequest::Brightness(level) => OsdContent::brightness(level),
+            OsdRequest::Level(level, icon) => OsdContent::Level {
+                icon: Symbol::lookup(icon, icon_gauge),
+                level: level.clamp(0., 1.),
+                muted: false,
+                adjusts: None,
+            },
+            OsdRequest::Toggle { on, label, icon } => OsdContent::Toggle {
+                icon: Symbol::lookup(
+                    icon,
+                    if on {
+                        icon_toggle_right
+                    } else {
+                        icon_toggle_left
+                    },
+                ),
+                label,
+                on,
+            },
+            OsdRequest::Text(text, icon) => OsdContent::Text {
+                icon: icon
+                    .and_then(|name| icons::lookup(&name))
+                    .map(Symbol::Themed),
+                text,
+            },
+        }
+    }
+}
+
+pub struct Osd {
+    pub animation: Animation<f32>,
+    content: OsdContent,
+    /// The default sink, `None` until it is read. The first reading is not a change to show.
+    sink: Option<SinkVolume>,
+    /// Brightness of the configured display, `None` until it is read.
+    brightness: Option<f32>,
+    /// Bumped on every show, so only the timer of the latest one hides the OSD.
+    shown: u64,
+    /// Whether the OSD is shown or fading out. Interactive OSDs have no surface otherwise, so
+    /// they do not catch the pointer.
+    visible: bool,
+    hovered: bool,
+    /// Whether the level is being dragged, from a press on the OSD to its release.
+    dragging: bool,
+    /// Last percentage set by dragging, so moves within it are not sent again.
+    dragged: Option<u8>,
+    cursor: Point,
+    config: OsdConfig,
+    brightness_config: BrightnessConfig,
+    /// Compositor telling which output has the focus.
+    compositor: Backend,
+    outputs: Vec<String>,
+    /// Output holding the keyboard focus, as last told by the compositor.
+    focused: Option<String>,
+    now: Instant,
+}
+
+impl Osd {
+    pub fn new(
+        config: OsdConfig,
+        brightness_config: BrightnessConfig,
+        compositor: Backend,
+        now: Instant,
+    ) -> Self {
+        Self {
+            animation: Animation::new(0.).quick(),
+            content: OsdContent::volume(SinkVolume {
+                volume: 0.,
+                muted: false,
+            }),
+            sink: None,
+            brightness: None,
+            shown: 0,
+            visible: false,
+            hovered: false,
+            dragging: false,
+            dragged: None,
+            cursor: Point::ORIGIN,
+            config,
+            brightness_config,
+            compositor,
+            outputs: Vec::new(),
+            focused: None,
+            now,
+        }
+    }
+
+    /// Brings the OSD up with `content`, and hides it once nothing changed for [`SHOWN_FOR`]
+    /// seconds.
+    fn show(&mut self, content: OsdContent) -> Task<Message> {
+        self.content = content;
+        self.animation.go_mut(1.0, self.now);
+        self.visible = true;
+        self.shown += 1;
+
+        let shown = self.shown;
+        Task::future(async move {
+            Timer::after(seconds(SHOWN_FOR)).await;
+            Message::Osd(OsdMessage::Disappear(shown))
+        })
+    }
+
+    /// Changes the level shown, when it is one the OSD can change. The new level comes back
+    /// through the subscriptions, bringing the OSD up again.
+    fn adjust(&self, change: BrightnessChange) -> Task<Message> {
+        let OsdContent::Level {
+            adjusts: Some(adjusts),
+            ..
+        } = &self.content
+        else {
+            return Task::none();
+        };
+
+        match adjusts {
+            Adjustable::Volume => Task::future(async move {
+                let result = match change {
+                    BrightnessChange::To(level) => sink::set_volume(level).await,
+                    BrightnessChange::By(step) => sink::change_volume(step).await,
+                };
+
+                if let Err(err) = result {
+                    tracing::warn!("Could not change the volume: {err}");
+                }
+            })
+            .discard(),
+            Adjustable::Brightness => self.change_brightness(change),
+        }
+    }
+
+    /// Monitors reached over DDC/CI cannot be watched, their new level is sent once set.
+    fn change_brightness(&self, change: BrightnessChange) -> Task<Message> {
+        let config = self.brightness_config.clone();
+
+        Task::future(async move {
+            match brightness::change(&config, change).await {
+                Ok(level) => Some(Message::Osd(OsdMessage::BrightnessChanged(level))),
+                Err(err) => {
+                    tracing::warn!("Could not change the brightness: {err}");
+                    None
+                }
+            }
+        })
+        .and_then(Task::done)
+    }
+
+    /// Sets the level to the one under the pointer, along the bar.
+    fn drag(&mut self) -> Task<Message> {
+        let level = components::Volume::level_at(self.cursor.x, SIZE.0 as f32);
+        let percentage = (level * 100.).round() as u8;
+        if self.dragged.replace(percentage) == Some(percentage) {
+            return Task::none();
+        }
+
+        self.adjust(BrightnessChange::To(level))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum OsdMessage {
+    Show(OsdRequest),
+    /// Content whose icon was looked up, ready to be shown.
+    Display(OsdContent),
+    SinkChanged(SinkVolume),
+    BrightnessChanged(f32),
+    ChangeBrightness(BrightnessChange),
+    FocusedOutput(Option<String>),
+    Appear,
+    /// Hides the OSD, unless it was shown again since this was sent.
+    Disappear(u64),
+    /// Drops the surfaces of an interactive OSD once faded out, unless it was shown again.
+    Hidden(u64),
+
+    Hovered(bool),
+    Pressed,
+    Released,
+    Moved(Point),
+    /// Steps of the mouse wheel, positive when scrolling up.
+    Scrolled(f32),
+    ToggleMute,
+}
+
+impl Feature for Osd {
+    type InnerMessage = OsdMessage;
+
+    fn layer_settings(&self) -> iced_layershell::reexport::NewLayerShellSettings {
+        NewLayerShellSettings {
+            size: Some(SIZE),
+            layer: Layer::Overlay,
+            anchor: Anchor::Bottom | Anchor::Right,
+            events_transparent: !self.config.interactive,
+            keyboard_interactivity: KeyboardInteractivity::None,
+            output_option: self
+                .output()
+                .map(|o| OutputOption::OutputName(o.to_string()))
+                .unwrap_or(OutputOption::None),
+            exclusive_zone: Some(-1),
+            ..Default::default()
+        }
+    }
+
+    fn update(&mut self, message: OsdMessage) -> iced::Task<Message> {
+        match message {
+            OsdMessage::Show(OsdRequest::Volume(volume)) => {
+                let sink = SinkVolume {
+                    volume: volume.clamp(0., 1.),
+                    muted: self.sink.is_some_and(|s| s.muted),
+                };
+                self.sink = Some(sink);
+                self.show(OsdContent::volume(sink))
+            }
+            OsdMessage::Show(OsdRequest::Brightness(level)) => {
+                self.brightness = Some(level.clamp(0., 1.));
+                self.show(OsdContent::brightness(level))
+            }
+            OsdMessage::Show(request) => {
+                Task::future(smol::unblock(move || OsdContent::lookup(request)))
+                    .map(|content| Message::Osd(OsdMessage::Display(content)))
+            }
+            OsdMessage::Display(content) => self.show(content),
+            OsdMessage::SinkChanged(sink) => {
+                if self.sink.replace(sink).is_some() {
+                    self.show(OsdContent::volume(sink))
+                } else {
+                    Task::none()
+                }
+            }
+            OsdMessage::BrightnessChanged(level) => {
+                if self.brightness.replace(level).is_some() {
+                    self.show(OsdContent::brightness(level))
+                } else {
+                    Task::none()
+                }
+            }
+            OsdMessage::ChangeBrightness(change) => self.change_brightness(change),
+            OsdMessage::FocusedOutput(focused) => {
+                self.focused = focused;
+                Task::none()
+            }
+            OsdMessage::Appear => self.show(self.content.clone()),
+            OsdMessage::Disappear(shown) => {
+                // Hovering keeps it up, leaving it starts the timer again.
+                if shown != self.shown || self.hovered {
+                    return Task::none();
+                }
+
+                self.animation.go_mut(0.0, self.now);
+                Task::future(async move {
+                    Timer::after(milliseconds(FADE_OUT)).await;
+                    Message::Osd(OsdMessage::Hidden(shown))
+                })
+            }
+            OsdMessage::Hidden(shown) => {
+                if shown == self.shown {
+                    self.visible = false;
+                }
+                Task::none()
+            }
+            OsdMessage::Hovered(hovered) => {
+                self.hovered = hovered;
+                if hovered {
+                    Task::none()
+                } else {
+                    self.dragging = false;
+                    self.show(self.content.clone())
+                }
+            }
+            OsdMessage::Pressed => {
+                self.dragging = true;
+                self.drag()
+            }
+            OsdMessage::Released => {
+                self.dragging = false;
+                self.dragged = None;
+                Task::none()
+            }
+            OsdMessage::Moved(cursor) => {
+                self.cursor = cursor;
+                if self.dragging {
+                    self.drag()
+                } else {
+                    Task::none()
+                }
+            }
+            OsdMessage::Scrolled(steps) if steps == 0. => Task::none(),
+            OsdMessage::Scrolled(steps) => self.adjust(BrightnessChange::By(steps * VOLUME_STEP)),
+            OsdMessage::ToggleMute => match &self.content {
+                OsdContent::Level {
+                    adjusts: Some(Adjustable::Volume),
+                    ..
+                } => Task::future(async {
+                    if let Err(err) = sink::toggle_mute().await {
+                        tracing::warn!("Could not toggle the mute: {err}");
+                    }
+                })
+                .discard(),
+                _ => Task::none(),
+            },
+        }
+    }
+
+    fn view(&self) -> impl Into<iced::Element<'_, Message>> {
+        let alpha = self
+            .animation
+            .interpolate_with(|f| f, std::time::Instant::now());
+
+        let (bar, label, icon) = match &self.content {
+            OsdContent::Level {
+                icon, level, muted, ..
+            } => (
+                components::Volume {
+                    volume: *level,
+                    muted: *muted,
+                    hollow: false,
+                    alpha,
+                },
+                None,
+                Some(icon),
+            ),
+            OsdContent::Toggle { icon, label, on } => (
+                components::Volume {
+                    volume: 1.,
+                    muted: !on,
+                    hollow: false,
+                    alpha,
+                },
+                Some(components::label(label, true, alpha)),
+                Some(icon),
+            ),
+            OsdContent::Text { icon, text } => (
+                components::Volume {
+                    volume: 0.,
+                    muted: false,
+                    hollow: true,
+                    alpha,
+                },
+                Some(components::label(text, false, alpha)),
+                icon.as_ref(),
+            ),
+        };
+
+        let interactive = self.config.interactive;
+        let mutes = interactive
+            && matches!(
+                self.content,
+                OsdContent::Level {
+                    adjusts: Some(Adjustable::Volume),
+                    ..
+                }
+            );
+
+        let layers = iter::once(bar.into()).chain(label).chain(icon.map(|icon| {
+            components::icon(
+                icon,
+                alpha,
+                mutes.then_some(Message::Osd(OsdMessage::ToggleMute)),
+            )
+        }));
+        let content: Element<'_, Message> = container(Stack::with_children(layers))
+            .width(Fill)
+            .height(Fill)
+            .style(|_| container::Style {
+                ..Default::default()
+            })
+            .into();
+
+        if !interactive {
+            return content;
+        }
+
+        mouse_area(content)
+            .on_enter(Message::Osd(OsdMessage::Hovered(true)))
+            .on_exit(Message::Osd(OsdMessage::Hovered(false)))
+            .on_press(Message::Osd(OsdMessage::Pressed))
+            .on_release(Message::Osd(OsdMessage::Released))
+            .on_move(|cursor| Message::Osd(OsdMessage::Moved(cursor)))
+            .on_scroll(|delta| {
+                let steps = match delta {
+                    ScrollDelta::Lines { y, .. } => y,
+                    ScrollDelta::Pixels { y, .. } => y / 20.,
+                };
+                Message::Osd(OsdMessage::Scrolled(steps.round()))
+            })
+            .into()
+    }
+
+    fn subscriptions(&self) -> Subscription<Message> {
+        let focus = match self.config.output {
+            OsdOutput::Focused => subscriptions::focus_subscription(self.compositor),
+            OsdOutput::All | OsdOutput::Named(_) => Subscription::none(),
+        };
+
+        Subscription::batch([
+            subscriptions::volume_subscription(),
+            subscriptions::brightness_subscription(self.brightness_config.clone()),
+            focus,
+        ])
+        .map(Message::Osd)
+    }
+
+    fn reconfigure(&mut self, config: &Config) -> bool {
+        let before = (self.outputs(), self.config.interactive);
+        self.config = config.osd.clone();
+        self.brightness_config = config.brightness.clone();
+        self.compositor = config.compositor;
+        before != (self.outputs(), self.config.interactive)
+    }
+
+    fn output(&self) -> Option<&str> {
+        match &self.config.output {
+            OsdOutput::Focused => connected_output(self.focused.as_deref(), &self.outputs),
+            OsdOutput::Named(output) => connected_output(Some(output), &self.outputs),
+            OsdOutput::All => None,
+        }
+    }
+
+    fn outputs(&self) -> Vec<Option<String>> {
+        if self.config.interactive && !self.visible {
+            return Vec::new();
+        }
+
+        match &self.config.output {
+            OsdOutput::All if !self.outputs.is_empty() => {
+                self.outputs.iter().cloned().map(Some).collect()
+            }
+            _ => vec![self.output().map(str::to_string)],
+        }
+    }
+
+    fn retarget(&mut self, outputs: &[String]) -> bool {
+        let before = self.outputs();
+        self.outputs = outputs.to_vec();
+        before != self.outputs()
+    }
+
+    fn set_now(&mut self, now: Instant) {
+        self.now = now;
+    }
+
+    fn is_animating(&self) -> bool {
+        self.animation.is_animating(self.now)
+    }
+}
@@ -0,0 +1,209 @@
+use iced::{
+    Element,
+    Length::Fill,
+    Padding, Point, Rectangle, Renderer, Theme, Vector,
+    advanced::mouse,
+    widget::{
+        Canvas,
+        canvas::{self, LineCap, Path, Program, Stroke},
+        container, mouse_area, text,
+    },
+};
+
+use crate::{
+    Message,
+    features::osd::Symbol,
+    styles::{self, BLACK_FONT},
+};
+
+/// What the bar stands for, on a bead centered over the start of the bar, just above it.
+/// Pressing it sends `on_press`, when given.
+pub fn icon<'a>(icon: &Symbol, alpha: f32, on_press: Option<Message>) -> Element<'a, Message> {
+    const SIZE: f32 = 56.;
+
+    let icon = match icon {
+        Symbol::Lucide(icon) => icon()
+            .size(28)
+            .center()
+            .style(move |theme: &Theme| text::Style {
+                color: Some(theme.palette().text.scale_alpha(alpha)),
+            })
+            .into(),
+        Symbol::Themed(icon) => icon.view_faded(32, alpha),
+    };
+
+    let bead = container(icon)
+        .center(SIZE)
+        .style(move |theme| container::Style {
+            background: Some(theme.palette().background.scale_alpha(alpha).into()),
+            ..styles::bead_style(theme)
+        });
+    let bead: Element<'a, Message> = match on_press {
+        Some(message) => mouse_area(bead).on_press(message).into(),
+        None => bead.into(),
+    };
+
+    container(bead)
+        .align_left(Fill)
+        .align_bottom(Fill)
+        .padding(
+            Padding::ZERO
+                .left(Volume::MARGIN * 2. - SIZE / 2.)
+                .bottom(Volume::MARGIN + Volume::THICKNESS / 2. + 8.),
+        )
+        .into()
+}
+
+/// A short text centered on the bar. Drawn in the background color when the bar is `filled`.
+pub fn label<'a>(label: &str, filled: bool, alpha: f32) -> Element<'a, Message> {
+    let label = text(label.to_string())
+        .size(18)
+        .font(BLACK_FONT)
+        .wrapping(text::Wrapping::None)
+        .style(move |theme: &Theme| {
+            let palette = theme.palette();
+            let color = if filled {
+                palette.background
+            } else {
+                palette.text
+            };
+
+            text::Style {
+                color: Some(color.scale_alpha(alpha)),
+            }
+        });
+
+    let bar = container(label)
+        .center_x(Fill)
+        .center_y(Volume::THICKNESS)
+        .clip(true);
+
+    container(bar)
+        .width(Fill)
+        .align_bottom(Fill)
+        .padding(
+            Padding::ZERO
+                .left(Volume::MARGIN * 2.)
+                .right(Volume::MARGIN * 2.5)
+                .bottom(Volume::MARGIN - Volume::THICKNESS / 2.),
+        )
+        .into()
+}
+
+pub struct Volume {
+    pub volume: f32,
+    /// Draws the level greyed out.
+    pub muted: bool,
+    /// Draws only the background of the bar, for a label to go on it.
+    pub hollow: bool,
+    pub alpha: f32,
+}
+
+impl Volume {
+    const MARGIN: f32 = 68.;
+    /// Width of the bar's background.
+    const THICKNESS: f32 = 40.;
+
+    /// Level under a point `x` pixels from the left of a canvas `width` pixels wide.
+    pub fn level_at(x: f32, width: f32) -> f32 {
+        let start = Self::MARGIN * 2.;
+        let end = width - Self::MARGIN * 2.5;
+
+        ((x - start) / (end - start)).clamp(0., 1.)
+    }
+}
+
+impl<'a> From<Volume> for Element<'a, Message> {
+    fn from(value: Volume) -> Self {
+        Canvas::new(value).width(Fill).height(Fill).into()
+    }
+}
+
+impl<T> Program<T> for Volume {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let alpha = self.alpha;
+
+        let va1 = Vector::new(0., -Self::MARGIN);
+        let va2 = Vector::new(Self::MARGIN, 0.);
+        let vb = Vector::new(Self::MARGIN, -Self::MARGIN);
+
+        let start_point = Point::new(Volume::MARGIN, bounds.height);
+        let (a1, b1) = (start_point + va1, start_point + vb);
+        let next_point = Point::new(bounds.width - Self::MARGIN * 2.5, b1.y);
+        let (a2, b2) = (next_point + va2, next_point + vb);
+        let (a3, b3) = (b2 + va1, b2 + vb);
+        let last_point = Point::new(bounds.width, b3.y);
+
+        let background = theme.palette().background;
+        let primary = if self.muted {
+            theme.palette().text.scale_alpha(0.4)
+        } else {
+            theme.palette().primary
+        };
+
+        frame.stroke(
+            &Path::new(|b| {
+                b.move_to(start_point);
+                b.quadratic_curve_to(a1, b1);
+                b.line_to(next_point);
+                b.quadratic_curve_to(a2, b2);
+                b.quadratic_curve_to(a3, b3);
+                b.line_to(last_point);
+            }),
+            Stroke::default()
+                .with_width(8.)
+                .with_line_cap(LineCap::Round)
+                .with_color(background.scale_alpha(alpha)),
+        );
+
+        frame.stroke(
+            &Path::new(|b| {
+                b.move_to(b1);
+                b.line_to(next_point);
+            }),
+            Stroke::default()
+                .with_width(Self::THICKNESS)
+                .with_line_cap(LineCap::Round)
+                .with_color(background.scale_alpha(alpha)),
+        );
+
+        if self.hollow {
+            return vec![frame.into_geometry()];
+        }
+
+        frame.stroke(
+            &Path::new(|b| {
+                b.move_to(b1);
+                b.line_to(next_point);
+            }),
+            Stroke::default()
+                .with_width(8.)
+                .with_line_cap(LineCap::Round)
+                .with_color(primary.scale_alpha(alpha)),
+        );
+
+        let db1n = b1.distance(next_point);
+        frame.stroke(
+            &Path::new(|b| {
+                b.move_to(b1);
+                b.line_to(next_point - Vector::new(db1n * (1. - self.volume), 0.));
+            }),
+            Stroke::default()
+                .with_width(32.)
+                .with_line_cap(LineCap::Round)
+                .with_color(primary.scale_alpha(alpha)),
+        );
+
+        vec![frame.into_geometry()]
+    }
+}
@@ -0,0 +1,153 @@
+use std::{io, os::fd::AsFd};
+
+use iced::{
+    Subscription,
+    futures::{
+        SinkExt,
+        channel::mpsc::{SendError, Sender},
+        future,
+    },
+    stream::channel,
+};
+use inotify::{Inotify, WatchMask};
+use smol::{Async, stream::StreamExt};
+
+use crate::{
+    brightness::Device,
+    compositor::{Backend, CompositorEvent},
+    config::BrightnessConfig,
+    error::{Backoff, Error},
+    features::osd::OsdMessage,
+    sink,
+};
+
+/// The default sink, once when subscribing and then every time it changes.
+pub(super) fn volume_subscription() -> Subscription<OsdMessage> {
+    Subscription::run_with(0x70, |_| {
+        channel(4, async |mut sender| {
+            let mut backoff = Backoff::default();
+
+            loop {
+                let err = {
+                    let mut sinks = sender.clone().with(|volume| {
+                        backoff.reset();
+                        future::ready(Ok::<_, SendError>(OsdMessage::SinkChanged(volume)))
+                    });
+                    sink::follow(&mut sinks)
+                        .await
+                        .err()
+                        .unwrap_or(Error::Stopped("pactl subscribe"))
+                };
+
+                tracing::warn!("Lost track of the volume: {err}");
+                backoff.wait().await;
+            }
+        })
+    })
+}
+
+/// The configured display's brightness, once when subscribing and then every time it changes.
+pub(super) fn brightness_subscription(config: BrightnessConfig) -> Subscription<OsdMessage> {
+    Subscription::run_with(config, |config| {
+        let config = config.clone();
+
+        channel(4, async move |mut sender| {
+            let mut backoff = Backoff::default();
+
+            loop {
+                let err = follow_brightness(&config, &mut sender, &mut backoff)
+                    .await
+                    .err()
+                    .unwrap_or(Error::Stopped("the brightness watch"));
+
+                tracing::warn!("Lost track of the brightness: {err}");
+                backoff.wait().await;
+            }
+        })
+    })
+}
+
+async fn follow_brightness(
+    config: &BrightnessConfig,
+    sender: &mut Sender<OsdMessage>,
+    backoff: &mut Backoff,
+) -> Result<(), Error> {
+    let Some(device) = Device::open(config)? else {
+        tracing::info!("No backlight device, the brightness OSD only shows IPC changes");
+        return iced::futures::future::pending().await;
+    };
+
+    let watch_failed = |err| Error::Io("could not watch the brightness", err);
+    let mut inotify = Inotify::init().map_err(watch_failed)?;
+    for file in device.watched_files().iter().filter(|f| f.exists()) {
+        inotify
+            .watches()
+            .add(file, WatchMask::MODIFY | WatchMask::CLOSE_WRITE)
+            .map_err(watch_failed)?;
+    }
+    let readable = Async::new(inotify.as_fd().try_clone_to_owned().map_err(watch_failed)?)
+        .map_err(watch_failed)?;
+
+    // Read once watched, so no change falls in between.
+    let mut current = device.level().await?;
+    let _ = sender.send(OsdMessage::BrightnessChanged(current)).await;
+    backoff.reset();
+
+    if device.watched_files().is_empty() {
+        return iced::futures::future::pending().await;
+    }
+
+    let mut buffer = [0; 1024];
+    loop {
+        readable.readable().await.map_err(watch_failed)?;
+
+        let changed = match inotify.read_events(&mut buffer) {
+            Ok(mut events) => events.next().is_some(),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => false,
+            Err(err) => return Err(watch_failed(err)),
+        };
+
+        if changed {
+            let level = device.level().await?;
+            if level != current {
+                current = level;
+                let _ = sender.send(OsdMessage::BrightnessChanged(current)).await;
+            }
+        }
+    }
+}
+
+/// The output holding the keyboard focus, every time it changes.
+pub(super) fn focus_subscription(backend: Backend) -> Subscription<OsdMessage> {
+    Subscription::run_with(backend, |backend| {
+        let backend = *backend;
+
+        channel(4, async move |mut sender| {
+            let mut backoff = Backoff::default();
+
+            loop {
+                let mut events = backend.connect().events();
+                let mut focused = None;
+
+                let err = loop {
+                    match events.next().await {
+                        Some(Ok(CompositorEvent::FocusedOutput(output))) => {
+                            backoff.reset();
+
+                            if focused.as_ref() != Some(&output) {
+                                focused = Some(output.clone());
+                                let _ = sender.send(OsdMessage::FocusedOutput(output)).await;
+                            }
+                        }
+                        Some(Ok(_)) => backoff.reset(),
+                        Some(Err(err)) => break Error::from(err),
+                        None => break Error::Stopped("the compositor event stream"),
+                    }
+                };
+
+                tracing::warn!("Lost track of the focused output: {err}");
+                backoff.wait().await;
+            }
+        })
+    })
+}
@@ -37,8 +37,13 @@ impl PowerMenu {
 
     fn execute_command(mut cmd: Command) -> Task<Message> {
         Task::future(async move {
-            let mut child = cmd.spawn().unwrap();
-            child.status().await.unwrap();
+            match cmd.status().await {
+                Ok(status) if !status.success() => {
+                    tracing::warn!("{cmd:?} exited with {status}");
+                }
+                Ok(_) => {}
+                Err(err) => tracing::error!("Could not run {cmd:?}: {err}"),
+            }
 
             Message::Animation
         })
@@ -76,7 +81,8 @@ impl Feature for PowerMenu {
             PowerMenuMessage::Logout => Self::execute_command({
                 let mut cmd = Command::new("loginctl");
                 cmd.arg("terminate-session");
-                cmd.arg(std::env::var("XDG_SESSION_ID").unwrap());
+                // logind resolves `self` to the session of the caller.
+                cmd.arg(std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "self".into()));
                 cmd
             }),
         }
@@ -1,61 +1,282 @@
-use std::time::Instant;
+use std::{collections::HashMap, time::Instant};
 
 use chrono::{DateTime, Local};
 use iced::{
-    Color,
     Length::{Fill, Shrink},
-    Padding, Subscription, Task,
-    alignment::Vertical,
-    widget::{button, container, row},
+    Subscription, Task,
+    alignment::Horizontal,
+    widget::container,
 };
 use iced_layershell::reexport::{
     Anchor, KeyboardInteractivity, Layer, NewLayerShellSettings, OutputOption,
 };
-use lucide_icons::iced::icon_box;
 use wayland_client::protocol::wl_output::{self, WlOutput};
 
-use crate::{FeatureSelector, Message, features::Feature};
+use crate::{
+    Message,
+    compositor::{Backend, Window, Workspace},
+    config::{Config, Slot, StatusBarConfig},
+    features::Feature,
+    icons::Icon,
+    sink::{self, SinkVolume},
+    tray::{self, TrayItem},
+};
 
 mod components;
+mod modules;
 mod subscriptions;
+mod waves_player;
+
+pub use modules::Module;
+pub use subscriptions::visualizer_subscription;
+pub use waves_player::{PlaybackStatus, Player};
 
 #[derive(Clone, Debug)]
 pub struct StatusBar {
     now: Instant,
     pub output: String,
     pub wloutput: WlOutput,
+    compositor: Backend,
+    config: StatusBarConfig,
     pub(crate) cava_info: Vec<f32>,
     pub(crate) current_datetime: DateTime<Local>,
-    pub(crate) current_workspace: i32,
+    /// Workspaces of this output, regular ones first.
+    pub(crate) workspaces: Vec<Workspace>,
+    /// Whether the output holds the keyboard focus, which dims its strip otherwise.
+    pub(crate) focused: bool,
+    pub(crate) window: Option<FocusedWindow>,
+    pub(crate) tray: Vec<TrayItem>,
+    /// Media players of the session, the one that most recently started playing first.
+    pub(crate) players: Vec<Player>,
+    /// The default sink, `None` until it is read.
+    pub(crate) sink: Option<SinkVolume>,
+    /// Modules whose source is unavailable, with the reason, until it comes back.
+    errors: HashMap<Module, String>,
 }
 
 impl StatusBar {
     pub fn new(
         output: impl Into<String>,
         wloutput: WlOutput,
-        current_workspace: i32,
+        compositor: Backend,
+        config: StatusBarConfig,
         now: Instant,
     ) -> Self {
         Self {
             now,
             output: output.into(),
             wloutput,
+            compositor,
+            config,
             cava_info: Vec::with_capacity(12),
             current_datetime: Local::now(),
-            current_workspace,
+            workspaces: Vec::new(),
+            focused: true,
+            window: None,
+            tray: Vec::new(),
+            players: Vec::new(),
+            sink: None,
+            errors: HashMap::new(),
+        }
+    }
+
+    fn focus_workspace(&self, workspace: Workspace) -> Task<Message> {
+        let backend = self.compositor;
+
+        Task::future(async move {
+            if let Err(err) = backend.connect().focus_workspace(&workspace).await {
+                tracing::warn!("Could not switch to workspace {}: {err}", workspace.name);
+            }
+        })
+        .discard()
+    }
+
+    /// Side of the bar holding the tray, where its menus open.
+    fn tray_side(&self) -> Horizontal {
+        let layout = self.config.layout(&self.output);
+        let holds_tray = |slots: &[Slot]| slots.iter().any(|s| s.contains(Module::Tray));
+
+        if holds_tray(&layout.left) {
+            Horizontal::Left
+        } else if holds_tray(&layout.center) {
+            Horizontal::Center
+        } else {
+            Horizontal::Right
+        }
+    }
+
+    fn tray_action(&self, action: TrayAction) -> Task<Message> {
+        let output = self.output.clone();
+
+        match action {
+            TrayAction::Activate(id) => {
+                let only_menu = self.tray.iter().any(|i| i.id == id && i.item_is_menu);
+                if only_menu {
+                    return self.tray_action(TrayAction::OpenMenu(id));
+                }
+
+                // Plenty of items, those of libappindicator among them, only have a menu.
+                Task::future(async move {
+                    let err = tray::activate(&id).await.err()?;
+                    tracing::debug!("Could not activate tray item {id}, opening its menu: {err}");
+                    Some(Message::StatusBar(StatusBarMessage::Tray(
+                        output,
+                        TrayAction::OpenMenu(id),
+                    )))
+                })
+                .and_then(Task::done)
+            }
+            TrayAction::SecondaryActivate(id) => Task::future(async move {
+                if let Err(err) = tray::secondary_activate(&id).await {
+                    tracing::debug!("Could not activate tray item {id}: {err}");
+                }
+            })
+            .discard(),
+            TrayAction::Scroll(_, 0) => Task::none(),
+            TrayAction::Scroll(id, delta) => Task::future(async move {
+                if let Err(err) = tray::scroll(&id, delta).await {
+                    tracing::debug!("Could not scroll over tray item {id}: {err}");
+                }
+            })
+            .discard(),
+            TrayAction::OpenMenu(id) => {
+                let side = self.tray_side();
+
+                Task::future(async move {
+                    match tray::menu::fetch(&id).await {
+                        Ok(menu) => menu.map(|menu| Message::OpenTrayMenu(output, side, menu)),
+                        Err(err) => {
+                            tracing::warn!("Could not fetch the menu of tray item {id}: {err}");
+                            None
+                        }
+                    }
+                })
+                .and_then(Task::done)
+            }
         }
     }
 
+    /// The player shown by the media module: the playing one, or the last one that played.
+    pub(crate) fn player(&self) -> Option<&Player> {
+        self.players
+            .iter()
+            .find(|p| p.status == PlaybackStatus::Playing)
+            .or(self.players.first())
+    }
+
+    fn media_action(&self, action: MediaAction) -> Task<Message> {
+        Task::future(async move {
+            let (name, result) = match &action {
+                MediaAction::PlayPause(name) => (name, waves_player::play_pause(name).await),
+                MediaAction::Next(name) => (name, waves_player::next(name).await),
+                MediaAction::Previous(name) => (name, waves_player::previous(name).await),
+            };
+
+            if let Err(err) = result {
+                tracing::warn!("Could not control media player {name}: {err}");
+            }
+        })
+        .discard()
+    }
+
+    fn volume_action(&self, action: VolumeAction) -> Task<Message> {
+        Task::future(async move {
+            let result = match action {
+                VolumeAction::Scroll(step) => sink::change_volume(step).await,
+                VolumeAction::ToggleMute => sink::toggle_mute().await,
+            };
+
+            if let Err(err) = result {
+                tracing::warn!("Could not change the volume: {err}");
+            }
+        })
+        .discard()
+    }
+
+    pub fn shows(&self, module: Module) -> bool {
+        self.config.layout(&self.output).contains(module)
+    }
+
     fn is_in_main(&self) -> bool {
-        self.output == "DP-3"
+        self.config
+            .main_output
+            .as_ref()
+            .is_none_or(|main| *main == self.output)
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct FocusedWindow {
+    pub window: Window,
+    pub icon: Option<Icon>,
+}
+
+/// What a click or a scroll over a tray item asks for, the item being named by its id.
+#[derive(Debug, Clone)]
+pub enum TrayAction {
+    Activate(String),
+    SecondaryActivate(String),
+    Scroll(String, i32),
+    OpenMenu(String),
+}
+
+/// A media control pressed on the bar, the player being named by its bus name.
+#[derive(Debug, Clone)]
+pub enum MediaAction {
+    PlayPause(String),
+    Next(String),
+    Previous(String),
+}
+
+/// A scroll or a click over the volume module.
+#[derive(Debug, Clone, Copy)]
+pub enum VolumeAction {
+    /// Adds to the volume, `1` being 100%.
+    Scroll(f32),
+    ToggleMute,
+}
+
 #[derive(Debug, Clone)]
 pub enum StatusBarMessage {
     CavaInfo(Vec<f32>),
     UpdateDatetime(DateTime<Local>),
-    UpdateCurrenWorkspace(String, i32),
+    UpdateWorkspaces(String, Vec<Workspace>),
+    OutputFocused(String, bool),
+    UpdateActiveWindow(String, Option<FocusedWindow>),
+    FocusWorkspace(String, Workspace),
+    /// Moves to the next (`1`) or previous (`-1`) regular workspace of the output, wrapping
+    /// around.
+    CycleWorkspace(String, i32),
+    TrayItems(Vec<TrayItem>),
+    Tray(String, TrayAction),
+    Players(Vec<Player>),
+    Media(String, MediaAction),
+    Sink(SinkVolume),
+    Volume(String, VolumeAction),
+    /// A module lost its source, on a single output or on all of them.
+    Failed(Option<String>, Module, String),
+}
+
+impl StatusBarMessage {
+    /// Every bar receives every message, those meant for the bar of one output name it.
+    fn output(&self) -> Option<&str> {
+        match self {
+            StatusBarMessage::UpdateWorkspaces(output, _)
+            | StatusBarMessage::OutputFocused(output, _)
+            | StatusBarMessage::UpdateActiveWindow(output, _)
+            | StatusBarMessage::FocusWorkspace(output, _)
+            | StatusBarMessage::CycleWorkspace(output, _)
+            | StatusBarMessage::Tray(output, _)
+            | StatusBarMessage::Media(output, _)
+            | StatusBarMessage::Volume(output, _) => Some(output),
+            StatusBarMessage::Failed(output, _, _) => output.as_deref(),
+            StatusBarMessage::CavaInfo(_)
+            | StatusBarMessage::UpdateDatetime(_)
+            | StatusBarMessage::TrayItems(_)
+            | StatusBarMessage::Players(_)
+            | StatusBarMessage::Sink(_) => None,
+        }
+    }
 }
 
 impl Feature for StatusBar {
@@ -75,8 +296,13 @@ impl Feature for StatusBar {
     }
 
     fn update(&mut self, message: StatusBarMessage) -> iced::Task<Message> {
+        if message.output().is_some_and(|output| output != self.output) {
+            return Task::none();
+        }
+
         match message {
             StatusBarMessage::CavaInfo(info) => {
+                self.errors.remove(&Module::Cava);
                 self.cava_info = info;
                 Task::none()
             }
@@ -84,61 +310,100 @@ impl Feature for StatusBar {
                 self.current_datetime = datetime;
                 Task::none()
             }
-            StatusBarMessage::UpdateCurrenWorkspace(output, workspace) => {
-                if output == self.output {
-                    self.current_workspace = workspace;
+            StatusBarMessage::UpdateWorkspaces(_, workspaces) => {
+                self.errors.remove(&Module::Workspaces);
+                self.workspaces = workspaces;
+                Task::none()
+            }
+            StatusBarMessage::OutputFocused(_, focused) => {
+                self.focused = focused;
+                Task::none()
+            }
+            StatusBarMessage::UpdateActiveWindow(_, window) => {
+                self.errors.remove(&Module::Window);
+                self.window = window;
+                Task::none()
+            }
+            StatusBarMessage::FocusWorkspace(_, workspace) => self.focus_workspace(workspace),
+            StatusBarMessage::CycleWorkspace(_, step) => {
+                let regular = self
+                    .workspaces
+                    .iter()
+                    .filter(|w| !w.special)
+                    .collect::<Vec<_>>();
+                let Some(current) = regular.iter().position(|w| w.active) else {
+                    return Task::none();
+                };
+
+                let next = (current as i32 + step).rem_euclid(regular.len() as i32) as usize;
+                if next == current {
+                    return Task::none();
                 }
+
+                self.focus_workspace(regular[next].clone())
+            }
+            StatusBarMessage::TrayItems(items) => {
+                self.errors.remove(&Module::Tray);
+                self.tray = items;
+                Task::none()
+            }
+            StatusBarMessage::Tray(_, action) => self.tray_action(action),
+            StatusBarMessage::Players(players) => {
+                self.errors.remove(&Module::Media);
+                self.players = players;
+                Task::none()
+            }
+            StatusBarMessage::Media(_, action) => self.media_action(action),
+            StatusBarMessage::Sink(sink) => {
+                self.errors.remove(&Module::Volume);
+                self.sink = Some(sink);
+                Task::none()
+            }
+            StatusBarMessage::Volume(_, VolumeAction::Scroll(step)) if step == 0. => Task::none(),
+            StatusBarMessage::Volume(_, action) => self.volume_action(action),
+            StatusBarMessage::Failed(_, module, err) => {
+                self.errors.insert(module, err);
                 Task::none()
             }
         }
     }
 
     fn view(&self) -> impl Into<iced::Element<'_, Message>> {
-        let padding = if self.is_in_main() { 8 } else { 0 };
-
-        container(
-            row![
-                crate::components::BeadsChord::W24,
-                components::workspace(self.current_workspace).into(),
-                crate::components::BeadsChord::W24,
-                components::CavaPlayer(&self.cava_info),
-                crate::components::BeadsChord::FILL,
-                crate::components::bead(
-                    row![
-                        components::clock(self.current_datetime).into(),
-                        Some(
-                            button(icon_box().size(32).center())
-                                .style(|theme, status| {
-                                    let mut style = button::primary(theme, status);
-                                    style.text_color = theme.palette().text;
-                                    style.with_background(Color::TRANSPARENT)
-                                })
-                                .on_press(Message::Open(FeatureSelector::PowerMenu))
-                                .width(56)
-                                .height(56)
-                        )
-                        .filter(|_| self.is_in_main())
-                    ]
-                    .spacing(-12.)
-                )
-                .padding(Padding::ZERO.right(padding).left(padding)),
-                crate::components::BeadsChord::W24
-            ]
-            .align_y(Vertical::Center),
-        )
-        .height(Shrink)
-        .width(Fill)
+        container(modules::view(self, self.config.layout(&self.output)))
+            .height(Shrink)
+            .width(Fill)
     }
 
     fn subscriptions(&self) -> iced::Subscription<Message> {
-        Subscription::batch([
-            subscriptions::cava_subscription(),
-            subscriptions::clock_subscription(),
-            subscriptions::hyprland_subscription(self.output.clone()),
-        ])
+        let layout = self.config.layout(&self.output);
+
+        Subscription::batch(
+            [
+                Some(subscriptions::clock_subscription())
+                    .filter(|_| layout.contains(Module::Clock)),
+                Some(subscriptions::compositor_subscription(
+                    self.output.clone(),
+                    self.compositor,
+                ))
+                .filter(|_| layout.contains(Module::Workspaces) || layout.contains(Module::Window)),
+                Some(subscriptions::tray_subscription()).filter(|_| layout.contains(Module::Tray)),
+                Some(subscriptions::media_subscription())
+                    .filter(|_| layout.contains(Module::Media)),
+                Some(subscriptions::volume_subscription())
+                    .filter(|_| layout.contains(Module::Volume)),
+            ]
+            .into_iter()
+            .flatten(),
+        )
         .map(Message::StatusBar)
     }
 
+    fn reconfigure(&mut self, config: &Config) -> bool {
+        self.config = config.status_bar.clone();
+        self.compositor = config.compositor;
+        false
+    }
+
     fn set_now(&mut self, now: Instant) {
         self.now = now;
     }
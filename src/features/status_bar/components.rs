@@ -1,19 +1,338 @@
+use std::borrow::Cow;
+
 use chrono::{DateTime, Local};
 use iced::{
     Alignment::Center,
     Element,
-    Length::Fill,
-    Point, Renderer,
+    Length::{self, Fill},
+    Padding, Point, Renderer, border,
+    mouse::ScrollDelta,
     widget::{
+        Row, button,
         canvas::{self, Action, Frame, LineCap, Program, Stroke, path},
-        center, column, text,
+        center, column, container, image, mouse_area, row, text,
+        text::Wrapping,
+        tooltip,
+    },
+};
+use lucide_icons::iced::{
+    icon_app_window, icon_pause, icon_play, icon_skip_back, icon_skip_forward, icon_sparkles,
+    icon_volume_2, icon_volume_x,
+};
+
+use crate::{
+    Message,
+    components::{BeadsChord, bead_center},
+    compositor::Workspace,
+    config::Waves,
+    features::status_bar::{
+        FocusedWindow, MediaAction, PlaybackStatus, Player, StatusBarMessage, TrayAction,
+        VolumeAction,
     },
+    sink::{SinkVolume, VOLUME_STEP},
+    styles::{self, BLACK_FONT},
+    tray::{Status, TrayIcon, TrayItem},
 };
 
-use crate::{components::bead_center, styles::BLACK_FONT};
+/// One button per workspace: filled when shown, dimmed when empty and in the danger color when
+/// urgent. Scrolling over the strip cycles through the regular workspaces.
+pub fn workspaces<'a>(
+    output: &'a str,
+    workspaces: &'a [Workspace],
+    focused: bool,
+) -> impl Into<Element<'a, Message>> {
+    mouse_area(
+        row(workspaces.iter().map(|w| workspace(output, w, focused)))
+            .spacing(4)
+            .padding([0, 8])
+            .align_y(Center),
+    )
+    .on_scroll(|delta| {
+        let y = match delta {
+            ScrollDelta::Lines { y, .. } => y,
+            // Touchpads scroll in small steps, only a decided swipe switches.
+            ScrollDelta::Pixels { y, .. } if y.abs() >= 20. => y,
+            ScrollDelta::Pixels { .. } => 0.,
+        };
 
-pub fn workspace<'a, T: 'a>(workspace: i32) -> impl Into<Element<'a, T>> {
-    bead_center(text!("{}", workspace).font(BLACK_FONT).size(24)).width(56)
+        Message::StatusBar(StatusBarMessage::CycleWorkspace(
+            output.to_string(),
+            if y > 0. {
+                -1
+            } else if y < 0. {
+                1
+            } else {
+                0
+            },
+        ))
+    })
+}
+
+fn workspace<'a>(output: &str, workspace: &'a Workspace, focused: bool) -> Element<'a, Message> {
+    let label: Element<'_, Message> = if workspace.special {
+        icon_sparkles().size(20).center().into()
+    } else {
+        text(&workspace.name)
+            .font(BLACK_FONT)
+            .size(20)
+            .center()
+            .into()
+    };
+
+    let (active, urgent, occupied) = (workspace.active, workspace.urgent, workspace.occupied);
+
+    button(center(label))
+        .width(40)
+        .height(40)
+        .padding(0)
+        .style(move |theme, status| {
+            let palette = theme.palette();
+            let text_color = if active {
+                palette.background
+            } else if urgent {
+                palette.danger
+            } else if occupied {
+                palette.text
+            } else {
+                palette.text.scale_alpha(0.4)
+            };
+            let background = if active && focused {
+                Some(palette.primary.into())
+            } else if active {
+                Some(palette.primary.scale_alpha(0.5).into())
+            } else if matches!(status, button::Status::Hovered | button::Status::Pressed) {
+                Some(palette.text.scale_alpha(0.1).into())
+            } else {
+                None
+            };
+
+            button::Style {
+                background,
+                text_color,
+                border: border::rounded(i32::MAX),
+                ..Default::default()
+            }
+        })
+        .on_press(Message::StatusBar(StatusBarMessage::FocusWorkspace(
+            output.to_string(),
+            workspace.clone(),
+        )))
+        .into()
+}
+
+/// Icon and title of the focused window. A title longer than `max_length` characters is
+/// ellipsized and shown in full in a tooltip.
+pub fn active_window(window: &FocusedWindow, max_length: usize) -> Element<'_, Message> {
+    let title = match window.window.title.as_str() {
+        "" => window.window.class.as_str(),
+        title => title,
+    };
+    let shown = ellipsize(title, max_length);
+    let truncated = matches!(shown, Cow::Owned(_));
+
+    let content = row![
+        window.icon.as_ref().map(|icon| icon.view(32)),
+        text(shown).wrapping(Wrapping::None),
+    ]
+    .spacing(8)
+    .padding([0, 16])
+    .align_y(Center);
+
+    if truncated {
+        tooltip(
+            content,
+            bead_center(text(title)).padding([0, 16]),
+            tooltip::Position::Bottom,
+        )
+        .into()
+    } else {
+        content.into()
+    }
+}
+
+fn ellipsize(text: &str, max_length: usize) -> Cow<'_, str> {
+    if text.chars().count() <= max_length {
+        return Cow::Borrowed(text);
+    }
+
+    let mut ellipsized = text
+        .chars()
+        .take(max_length.saturating_sub(1))
+        .collect::<String>();
+    ellipsized.push('…');
+
+    Cow::Owned(ellipsized)
+}
+
+/// Title and artist of the track with the controls the player supports. Both are ellipsized
+/// past `max_length` characters.
+pub fn media<'a>(output: &'a str, player: &'a Player, max_length: usize) -> Element<'a, Message> {
+    let control = |icon: Element<'a, Message>, action: Option<MediaAction>| {
+        button(center(icon))
+            .width(40)
+            .height(40)
+            .padding(0)
+            .style(|theme, status| {
+                let palette = theme.palette();
+                let background =
+                    matches!(status, button::Status::Hovered | button::Status::Pressed)
+                        .then(|| palette.text.scale_alpha(0.1).into());
+                let text_color = if status == button::Status::Disabled {
+                    palette.text.scale_alpha(0.4)
+                } else {
+                    palette.text
+                };
+
+                button::Style {
+                    background,
+                    text_color,
+                    border: border::rounded(i32::MAX),
+                    ..Default::default()
+                }
+            })
+            .on_press_maybe(action.map(|action| {
+                Message::StatusBar(StatusBarMessage::Media(output.to_string(), action))
+            }))
+    };
+    let name = &player.name;
+    let controllable = |can: bool| can && player.can_control;
+
+    let play_pause = if player.status == PlaybackStatus::Playing {
+        icon_pause()
+    } else {
+        icon_play()
+    };
+
+    row![
+        column![
+            text(ellipsize(&player.title, max_length))
+                .font(BLACK_FONT)
+                .wrapping(Wrapping::None),
+            (!player.artist.is_empty()).then(|| {
+                text(ellipsize(&player.artist, max_length))
+                    .size(12)
+                    .wrapping(Wrapping::None)
+            }),
+        ],
+        row![
+            control(
+                icon_skip_back().size(20).center().into(),
+                controllable(player.can_go_previous).then(|| MediaAction::Previous(name.clone())),
+            ),
+            control(
+                play_pause.size(20).center().into(),
+                controllable(true).then(|| MediaAction::PlayPause(name.clone())),
+            ),
+            control(
+                icon_skip_forward().size(20).center().into(),
+                controllable(player.can_go_next).then(|| MediaAction::Next(name.clone())),
+            ),
+        ],
+    ]
+    .spacing(8)
+    .padding(Padding::ZERO.left(24).right(8))
+    .align_y(Center)
+    .into()
+}
+
+/// Icon and percentage of the default sink, greyed out when muted. Scrolling changes the volume
+/// by 5% a step and clicking mutes it.
+pub fn volume<'a>(output: &'a str, sink: SinkVolume) -> Element<'a, Message> {
+    let icon = if sink.muted {
+        icon_volume_x()
+    } else {
+        icon_volume_2()
+    };
+    let message = move |action: VolumeAction| {
+        Message::StatusBar(StatusBarMessage::Volume(output.to_string(), action))
+    };
+
+    let content = row![
+        icon.size(20).center(),
+        text(format!("{:.0}%", sink.volume * 100.)).font(BLACK_FONT),
+    ]
+    .spacing(8)
+    .padding([0, 24])
+    .align_y(Center);
+    let content = container(content).style(move |theme: &iced::Theme| container::Style {
+        text_color: sink.muted.then(|| theme.palette().text.scale_alpha(0.4)),
+        ..Default::default()
+    });
+
+    mouse_area(content)
+        .on_press(message(VolumeAction::ToggleMute))
+        .on_scroll(move |delta| {
+            let steps = match delta {
+                ScrollDelta::Lines { y, .. } => y,
+                ScrollDelta::Pixels { y, .. } => y / 20.,
+            };
+            message(VolumeAction::Scroll(steps.round() * VOLUME_STEP))
+        })
+        .into()
+}
+
+/// A bead per tray item, passive ones left out. `None` when no item is left to show.
+pub fn tray<'a>(output: &'a str, items: &'a [TrayItem]) -> Option<Element<'a, Message>> {
+    let mut beads = Vec::with_capacity(items.len() * 2);
+
+    for item in items.iter().filter(|i| i.status != Status::Passive) {
+        if !beads.is_empty() {
+            beads.push(BeadsChord::W8.into());
+        }
+        beads.push(tray_item(output, item));
+    }
+
+    (!beads.is_empty()).then(|| Row::with_children(beads).align_y(Center).into())
+}
+
+/// Left click activates the item, middle click is its secondary action and right click opens
+/// its menu. An item asking for attention is ringed in the danger color.
+fn tray_item<'a>(output: &'a str, item: &'a TrayItem) -> Element<'a, Message> {
+    let icon: Element<'_, Message> = match &item.icon {
+        Some(TrayIcon::Themed(icon)) => icon.view(24),
+        Some(TrayIcon::Pixmap(handle)) => image(handle.clone()).width(24).height(24).into(),
+        None => icon_app_window().size(24).center().into(),
+    };
+    let message = move |action: TrayAction| {
+        Message::StatusBar(StatusBarMessage::Tray(output.to_string(), action))
+    };
+    let attention = item.status == Status::NeedsAttention;
+
+    let area = mouse_area(center(icon).width(56).height(56))
+        .on_press(message(TrayAction::Activate(item.id.clone())))
+        .on_middle_press(message(TrayAction::SecondaryActivate(item.id.clone())))
+        .on_right_press(message(TrayAction::OpenMenu(item.id.clone())))
+        .on_scroll(move |delta| {
+            let steps = match delta {
+                ScrollDelta::Lines { y, .. } => y,
+                ScrollDelta::Pixels { y, .. } => y / 20.,
+            };
+            message(TrayAction::Scroll(item.id.clone(), -steps.round() as i32))
+        });
+
+    let bead = bead_center(area).style(move |theme| {
+        let style = styles::bead_style(theme);
+        if attention {
+            style.border(
+                border::rounded(i32::MAX)
+                    .color(theme.palette().danger)
+                    .width(2),
+            )
+        } else {
+            style
+        }
+    });
+
+    if item.title.is_empty() {
+        bead.into()
+    } else {
+        tooltip(
+            bead,
+            bead_center(text(&item.title)).padding([0, 16]),
+            tooltip::Position::Bottom,
+        )
+        .into()
+    }
 }
 
 fn clock_text<'a, T: 'a>(datetime: DateTime<Local>, format: &str) -> impl Into<Element<'a, T>> {
@@ -35,7 +354,8 @@ pub fn clock<'a, T: 'a>(datetime: DateTime<Local>) -> impl Into<Element<'a, T>>
     .height(56)
 }
 
-pub struct CavaPlayer<'a>(pub &'a [f32]);
+/// Waves of the visualizer, drawn from the shared spectrum as the bar's [`Waves`] ask.
+pub struct CavaPlayer<'a>(pub &'a [f32], pub Waves);
 
 impl<'a> CavaPlayer<'a> {
     pub(crate) const WAVE_WIDTH: f32 = 24.;
@@ -43,10 +363,43 @@ impl<'a> CavaPlayer<'a> {
 
 impl<'a, T: 'a> From<CavaPlayer<'a>> for Element<'a, T> {
     fn from(value: CavaPlayer<'a>) -> Self {
-        let len = value.0.len();
-        canvas::Canvas::new(value)
-            .width(CavaPlayer::WAVE_WIDTH * (len + 1) as f32)
-            .into()
+        let width = match value.1.count {
+            Some(count) => Length::Fixed(CavaPlayer::WAVE_WIDTH * (count + 1) as f32),
+            None => Length::Fill,
+        };
+
+        canvas::Canvas::new(value).width(width).into()
+    }
+}
+
+/// The spectrum resampled to `count` heights, spread from the middle out when mirrored.
+fn resample(spectrum: &[f32], count: usize, mirrored: bool) -> Vec<f32> {
+    let sampled = if mirrored { count.div_ceil(2) } else { count };
+    let step = if sampled > 1 {
+        (spectrum.len() - 1) as f32 / (sampled - 1) as f32
+    } else {
+        0.
+    };
+
+    let heights = (0..sampled)
+        .map(|i| {
+            let position = i as f32 * step;
+            let (index, t) = (position as usize, position.fract());
+            let next = spectrum.get(index + 1).unwrap_or(&spectrum[index]);
+            spectrum[index] * (1. - t) + next * t
+        })
+        .collect::<Vec<_>>();
+
+    if mirrored {
+        // An odd count shares the middle wave between both halves.
+        heights
+            .iter()
+            .rev()
+            .chain(&heights[count % 2..])
+            .copied()
+            .collect()
+    } else {
+        heights
     }
 }
 
@@ -150,13 +503,22 @@ impl<'a, T> Program<T> for CavaPlayer<'a> {
 
         state.clear();
         state.push(Point::new(start_x, half_height));
-        state.extend(self.0.iter().enumerate().map(|(idx, f)| {
-            let direction = if idx % 2 == 0 { -1. } else { 1. };
-            Point::new(
-                start_x + (idx + 1) as f32 * Self::WAVE_WIDTH,
-                half_height - f * (half_height * 0.85 - 4.) * direction,
-            )
-        }));
+        let count = self
+            .1
+            .count
+            .unwrap_or_else(|| ((bounds.width / Self::WAVE_WIDTH) as usize).saturating_sub(1));
+        state.extend(
+            resample(self.0, count, self.1.mirrored)
+                .iter()
+                .enumerate()
+                .map(|(idx, f)| {
+                    let direction = if idx % 2 == 0 { -1. } else { 1. };
+                    Point::new(
+                        start_x + (idx + 1) as f32 * Self::WAVE_WIDTH,
+                        half_height - f * (half_height * 0.85 - 4.) * direction,
+                    )
+                }),
+        );
         state.push(Point::new(end_x, half_height));
 
         Some(Action::request_redraw())
@@ -0,0 +1,160 @@
+use std::iter;
+
+use iced::{
+    Color, Element,
+    Length::Fill,
+    Padding,
+    alignment::Vertical,
+    widget::{Row, button, container},
+};
+use lucide_icons::iced::icon_box;
+use serde::Deserialize;
+
+use crate::{
+    FeatureSelector, Message,
+    components::{BeadsChord, bead, bead_center, error},
+    config::{Layout, Slot},
+};
+
+use super::{StatusBar, components};
+
+/// Every module the bar knows how to draw, as named in the `layout` of the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Module {
+    Workspaces,
+    /// Icon and title of the focused window, when it is on this output.
+    Window,
+    /// Waves of the audio being played, named after the program that used to draw them.
+    Cava,
+    /// Track of the current media player with its controls, when there is a player.
+    Media,
+    /// Items of the system tray, each on a bead of its own.
+    Tray,
+    /// Level of the default sink. Scrolling over it changes the volume, clicking it mutes.
+    Volume,
+    Clock,
+    /// Button opening the power menu, only shown on the main output.
+    Power,
+}
+
+impl Module {
+    /// The module's content, `None` when it has nothing to show on this bar.
+    fn view(self, bar: &StatusBar) -> Option<Element<'_, Message>> {
+        if bar.errors.contains_key(&self) {
+            let label = match self {
+                Module::Cava => Some("No audio"),
+                _ => None,
+            };
+            return Some(container(error(label)).padding([0, 16]).into());
+        }
+
+        match self {
+            Module::Workspaces => {
+                Some(components::workspaces(&bar.output, &bar.workspaces, bar.focused).into())
+            }
+            Module::Window => bar
+                .window
+                .as_ref()
+                .map(|w| components::active_window(w, bar.config.max_title_length)),
+            Module::Cava => Some(
+                components::CavaPlayer(&bar.cava_info, bar.config.layout(&bar.output).waves).into(),
+            ),
+            Module::Media => bar
+                .player()
+                .map(|p| components::media(&bar.output, p, bar.config.max_title_length)),
+            Module::Tray => components::tray(&bar.output, &bar.tray),
+            Module::Volume => bar.sink.map(|sink| components::volume(&bar.output, sink)),
+            Module::Clock => Some(components::clock(bar.current_datetime).into()),
+            Module::Power => bar.is_in_main().then(|| {
+                button(icon_box().size(32).center())
+                    .style(|theme, status| {
+                        let mut style = button::primary(theme, status);
+                        style.text_color = theme.palette().text;
+                        style.with_background(Color::TRANSPARENT)
+                    })
+                    .on_press(Message::Open(FeatureSelector::PowerMenu))
+                    .width(56)
+                    .height(56)
+                    .into()
+            }),
+        }
+    }
+
+    /// The waves of the visualizer are drawn straight over the chord and tray items bring their own
+    /// beads, everything else sits on a bead.
+    fn beaded(self, bar: &StatusBar) -> bool {
+        !matches!(self, Module::Cava | Module::Tray) || bar.errors.contains_key(&self)
+    }
+}
+
+fn slot<'a>(bar: &'a StatusBar, slot: &Slot) -> Option<Element<'a, Message>> {
+    match slot {
+        Slot::Module(module) => {
+            let content = module.view(bar)?;
+
+            Some(if module.beaded(bar) {
+                bead_center(content).into()
+            } else {
+                content
+            })
+        }
+        Slot::Group(modules) => {
+            let contents = modules
+                .iter()
+                .filter_map(|module| module.view(bar))
+                .collect::<Vec<_>>();
+            let padding = if contents.len() > 1 { 8 } else { 0 };
+
+            (!contents.is_empty()).then(|| {
+                bead(
+                    Row::with_children(contents)
+                        .spacing(-12.)
+                        .align_y(Vertical::Center),
+                )
+                .padding(Padding::ZERO.right(padding).left(padding))
+                .into()
+            })
+        }
+    }
+}
+
+/// The slots of one section of the bar, joined by short chords.
+fn section<'a>(bar: &'a StatusBar, slots: &[Slot]) -> Vec<Element<'a, Message>> {
+    let mut elements = Vec::with_capacity(slots.len() * 2);
+
+    for element in slots.iter().filter_map(|s| slot(bar, s)) {
+        if !elements.is_empty() {
+            elements.push(BeadsChord::W24.into());
+        }
+        elements.push(element);
+    }
+
+    elements
+}
+
+/// Lays the bar out as configured. The left and right sections grow equally so the center one
+/// stays centered on the output.
+pub fn view<'a>(bar: &'a StatusBar, layout: &Layout) -> Element<'a, Message> {
+    let left = iter::once(BeadsChord::W24.into())
+        .chain(section(bar, &layout.left))
+        .chain(iter::once(BeadsChord::FILL.into()));
+    let right = iter::once(BeadsChord::FILL.into())
+        .chain(section(bar, &layout.right))
+        .chain(iter::once(BeadsChord::W24.into()));
+
+    Row::new()
+        .push(
+            Row::with_children(left)
+                .align_y(Vertical::Center)
+                .width(Fill),
+        )
+        .push(Row::with_children(section(bar, &layout.center)).align_y(Vertical::Center))
+        .push(
+            Row::with_children(right)
+                .align_y(Vertical::Center)
+                .width(Fill),
+        )
+        .align_y(Vertical::Center)
+        .into()
+}
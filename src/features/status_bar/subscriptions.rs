@@ -1,59 +1,122 @@
-use std::{convert::identity, process::Stdio, time::Duration};
+use std::time::Duration;
 
 use chrono::{Local, Timelike};
-use hyprland::{
-    data::Workspaces,
-    event_listener::{Event, EventStream},
-    shared::HyprData,
+use iced::{
+    Subscription,
+    futures::{SinkExt, channel::mpsc::SendError, future},
+    stream::channel,
+    time,
 };
-use iced::{Function, Subscription, futures::SinkExt, stream::channel, time};
-use smol::{
-    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
-    process::Command,
-    stream::StreamExt,
+use smol::stream::StreamExt;
+
+use crate::{
+    compositor::{Backend, CompositorEvent, WorkspaceModel},
+    config::VisualizerConfig,
+    error::{Backoff, Error},
+    features::status_bar::{FocusedWindow, Module, StatusBarMessage, waves_player},
+    icons, sink, tray, visualizer,
 };
 
-use crate::features::status_bar::StatusBarMessage;
+/// Spectrum of the audio visualizer, analyzed once for every bar.
+pub fn visualizer_subscription(config: VisualizerConfig) -> Subscription<StatusBarMessage> {
+    Subscription::run_with(config, |config| {
+        let config = config.clone();
 
-pub(super) fn cava_subscription() -> Subscription<StatusBarMessage> {
-    Subscription::run_with(0xCD, |_| {
-        let mut command = Command::new("cava");
-        command.stdout(Stdio::piped());
+        channel(5, async move |mut sender| {
+            let mut backoff = Backoff::default();
 
-        let mut child = command.spawn().expect("Could not run CAVA");
-        let stdout = child
-            .stdout
-            .take()
-            .expect("Child did not have a handle to stdout");
+            loop {
+                let err = {
+                    let mut frames = sender.clone().with(|frame| {
+                        backoff.reset();
+                        future::ready(Ok::<_, SendError>(StatusBarMessage::CavaInfo(frame)))
+                    });
+                    visualizer::run(&config, &mut frames)
+                        .await
+                        .err()
+                        .unwrap_or(Error::Stopped("the audio visualizer"))
+                };
 
-        let mut reader = BufReader::new(stdout).lines();
+                tracing::warn!("{err}");
+                let _ = sender
+                    .send(StatusBarMessage::Failed(
+                        None,
+                        Module::Cava,
+                        err.to_string(),
+                    ))
+                    .await;
 
-        channel(5, async move |mut sender| {
-            while let Some(Ok(line)) = reader.next().await {
-                let line = line
-                    .split(';')
-                    .filter_map(|i| i.parse().ok())
-                    .map(|f: f32| f / 1000.)
-                    .collect::<Vec<_>>();
-
-                if line.iter().any(|f| *f != 0.) {
-                    sender.send(StatusBarMessage::CavaInfo(line)).await.unwrap();
-                }
+                backoff.wait().await;
             }
         })
     })
 }
 
-// pub(super) fn playerctl_subscription() -> Subscription<StatusBarMessage> {
-//     Subscription::run(|| {
-//         let player_finder = PlayerFinder::new().unwrap();
-//         let player = player_finder.find_active().unwrap();
-//         PlayerEventsStream::new(&player)
-//             .skip_while(|e| matches!(e, mpris::Event::Paused | mpris::Event::Playing))
-//             .map(|e| matches!(e, mpris::Event::Playing))
-//             .map(StatusBarMessage::PlayerState)
-//     })
-// }
+/// Media players of the session, shared by every bar.
+pub(super) fn media_subscription() -> Subscription<StatusBarMessage> {
+    Subscription::run_with(0x3D, |_| {
+        channel(8, async |mut sender| {
+            let mut backoff = Backoff::default();
+
+            loop {
+                let err = {
+                    let mut players = sender.clone().with(|players| {
+                        backoff.reset();
+                        future::ready(Ok::<_, SendError>(StatusBarMessage::Players(players)))
+                    });
+                    waves_player::track(&mut players)
+                        .await
+                        .err()
+                        .unwrap_or(Error::Stopped("the media players"))
+                };
+
+                tracing::warn!("Lost the media players: {err}");
+                let _ = sender
+                    .send(StatusBarMessage::Failed(
+                        None,
+                        Module::Media,
+                        err.to_string(),
+                    ))
+                    .await;
+
+                backoff.wait().await;
+            }
+        })
+    })
+}
+
+/// The default sink, shared by every bar.
+pub(super) fn volume_subscription() -> Subscription<StatusBarMessage> {
+    Subscription::run_with(0x5C, |_| {
+        channel(4, async |mut sender| {
+            let mut backoff = Backoff::default();
+
+            loop {
+                let err = {
+                    let mut sinks = sender.clone().with(|volume| {
+                        backoff.reset();
+                        future::ready(Ok::<_, SendError>(StatusBarMessage::Sink(volume)))
+                    });
+                    sink::follow(&mut sinks)
+                        .await
+                        .err()
+                        .unwrap_or(Error::Stopped("pactl subscribe"))
+                };
+
+                tracing::warn!("Lost track of the volume: {err}");
+                let _ = sender
+                    .send(StatusBarMessage::Failed(
+                        None,
+                        Module::Volume,
+                        err.to_string(),
+                    ))
+                    .await;
+
+                backoff.wait().await;
+            }
+        })
+    })
+}
 
 pub(super) fn clock_subscription() -> Subscription<StatusBarMessage> {
     time::every(Duration::from_secs_f32(1.))
@@ -62,29 +125,131 @@ pub(super) fn clock_subscription() -> Subscription<StatusBarMessage> {
         .map(StatusBarMessage::UpdateDatetime)
 }
 
-pub(super) fn hyprland_subscription<'a: 'static>(
-    output_name: impl Into<String>,
+/// Workspaces of the output, whether it is focused and the focused window on it, from a single
+/// connection to the compositor.
+pub(super) fn compositor_subscription(
+    output_name: String,
+    backend: Backend,
 ) -> Subscription<StatusBarMessage> {
-    Subscription::run_with(output_name.into(), |output_name| {
-        let stream = EventStream::new();
-        let output_name = output_name.clone();
-
-        // FIX: Remove unwrap
-        let workspaces = Workspaces::get()
-            .unwrap()
-            .into_iter()
-            .filter(|w| w.monitor == output_name)
-            .collect::<Vec<_>>();
-
-        stream
-            .filter_map(Result::ok)
-            .map(move |event| match event {
-                Event::WorkspaceChanged(w) if workspaces.iter().any(|cw| cw.id == w.id) => {
-                    Some(w.id)
+    Subscription::run_with((output_name, backend), |(output_name, backend)| {
+        let (output_name, backend) = (output_name.clone(), *backend);
+
+        channel(8, async move |mut sender| {
+            let mut backoff = Backoff::default();
+
+            loop {
+                let mut events = backend.connect().events();
+                let mut model = WorkspaceModel::default();
+                // The first snapshot is always sent, it clears the error of a previous attempt.
+                let mut synced = false;
+                let mut focused = None;
+                let mut window = None;
+
+                let err = loop {
+                    match events.next().await {
+                        Some(Ok(event @ CompositorEvent::Workspaces(_))) => {
+                            backoff.reset();
+
+                            if model.apply(event).contains(&output_name) || !synced {
+                                synced = true;
+                                let workspaces = model.on(&output_name).to_vec();
+                                let _ = sender
+                                    .send(StatusBarMessage::UpdateWorkspaces(
+                                        output_name.clone(),
+                                        workspaces,
+                                    ))
+                                    .await;
+                            }
+                        }
+                        Some(Ok(event @ CompositorEvent::FocusedOutput(_))) => {
+                            model.apply(event);
+
+                            let is_focused = model.focused().is_none_or(|o| o == output_name);
+                            if focused != Some(is_focused) {
+                                focused = Some(is_focused);
+                                let _ = sender
+                                    .send(StatusBarMessage::OutputFocused(
+                                        output_name.clone(),
+                                        is_focused,
+                                    ))
+                                    .await;
+                            }
+                        }
+                        Some(Ok(CompositorEvent::ActiveWindow(active))) => {
+                            // A window without output is shown everywhere, it cannot be placed.
+                            let active = active
+                                .filter(|w| w.output.as_deref().is_none_or(|o| o == output_name));
+                            if window.as_ref() == Some(&active) {
+                                continue;
+                            }
+                            window = Some(active.clone());
+
+                            let focused_window = match active {
+                                Some(window) => {
+                                    let class = window.class.clone();
+                                    let icon = smol::unblock(move || icons::for_app(&class)).await;
+                                    Some(FocusedWindow { window, icon })
+                                }
+                                None => None,
+                            };
+                            let _ = sender
+                                .send(StatusBarMessage::UpdateActiveWindow(
+                                    output_name.clone(),
+                                    focused_window,
+                                ))
+                                .await;
+                        }
+                        Some(Err(err)) => break Error::from(err),
+                        None => break Error::Stopped("the compositor event stream"),
+                    }
+                };
+
+                tracing::warn!("Lost track of the compositor on {output_name}: {err}");
+                for module in [Module::Workspaces, Module::Window] {
+                    let _ = sender
+                        .send(StatusBarMessage::Failed(
+                            Some(output_name.clone()),
+                            module,
+                            err.to_string(),
+                        ))
+                        .await;
                 }
-                _ => None,
-            })
-            .filter_map(identity)
-            .map(StatusBarMessage::UpdateCurrenWorkspace.with(output_name))
+
+                backoff.wait().await;
+            }
+        })
+    })
+}
+
+/// Items of the system tray. The tray is the same on every output, so is the subscription.
+pub(super) fn tray_subscription() -> Subscription<StatusBarMessage> {
+    Subscription::run_with(0x7A, |_| {
+        channel(8, async |mut sender| {
+            let mut backoff = Backoff::default();
+
+            loop {
+                let err = {
+                    let mut items = sender.clone().with(|items| {
+                        backoff.reset();
+                        future::ready(Ok::<_, SendError>(StatusBarMessage::TrayItems(items)))
+                    });
+                    tray::host(&mut items)
+                        .await
+                        .err()
+                        .unwrap_or(Error::Stopped("the tray"))
+                };
+
+                tracing::warn!("Lost the tray: {err}");
+                let _ = sender
+                    .send(StatusBarMessage::Failed(
+                        None,
+                        Module::Tray,
+                        err.to_string(),
+                    ))
+                    .await;
+
+                backoff.wait().await;
+            }
+        })
     })
 }
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use iced::futures::{Sink, SinkExt, StreamExt, stream};
+use zbus::{
+    fdo::{DBusProxy, PropertiesProxy},
+    proxy,
+    proxy::CacheProperties,
+    zvariant::{OwnedValue, Value},
+};
+
+use crate::error::Error;
+
+const BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.Player",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+trait MprisPlayer {
+    fn play_pause(&self) -> zbus::Result<()>;
+    fn next(&self) -> zbus::Result<()>;
+    fn previous(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn metadata(&self) -> zbus::Result<HashMap<String, OwnedValue>>;
+    #[zbus(property)]
+    fn can_go_next(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn can_go_previous(&self) -> zbus::Result<bool>;
+    #[zbus(property)]
+    fn can_control(&self) -> zbus::Result<bool>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// A media player on the session bus, as far as the bar is concerned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    /// Bus name of the player, `org.mpris.MediaPlayer2.<name>`.
+    pub name: String,
+    pub title: String,
+    /// Artists of the track, comma separated.
+    pub artist: String,
+    pub status: PlaybackStatus,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    pub can_control: bool,
+}
+
+async fn player_proxy(
+    connection: &zbus::Connection,
+    name: &str,
+) -> zbus::Result<MprisPlayerProxy<'static>> {
+    // Players announce changes with `PropertiesChanged`, watched separately, so properties are
+    // always read from the player.
+    MprisPlayerProxy::builder(connection)
+        .destination(name.to_string())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+}
+
+async fn fetch(player: &MprisPlayerProxy<'_>, name: &str) -> zbus::Result<Player> {
+    let status = match player.playback_status().await?.as_str() {
+        "Playing" => PlaybackStatus::Playing,
+        "Paused" => PlaybackStatus::Paused,
+        _ => PlaybackStatus::Stopped,
+    };
+    let metadata = player.metadata().await.unwrap_or_default();
+
+    let title = match metadata.get("xesam:title").map(|v| &**v) {
+        Some(Value::Str(title)) => title.to_string(),
+        _ => String::new(),
+    };
+    let artist = match metadata.get("xesam:artist").map(|v| &**v) {
+        Some(Value::Array(artists)) => artists
+            .iter()
+            .filter_map(|a| match a {
+                Value::Str(a) => Some(a.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        // Some players send a single string despite the spec.
+        Some(Value::Str(artist)) => artist.to_string(),
+        _ => String::new(),
+    };
+
+    Ok(Player {
+        name: name.to_string(),
+        title,
+        artist,
+        status,
+        can_go_next: player.can_go_next().await.unwrap_or(false),
+        can_go_previous: player.can_go_previous().await.unwrap_or(false),
+        can_control: player.can_control().await.unwrap_or(true),
+    })
+}
+
+/// Sends the name of the player every time the properties of its `Player` interface change.
+async fn watch_player(
+    connection: zbus::Connection,
+    name: String,
+    changes: smol::channel::Sender<String>,
+) -> zbus::Result<()> {
+    let properties = PropertiesProxy::builder(&connection)
+        .destination(name.clone())?
+        .path(OBJECT_PATH)?
+        .build()
+        .await?;
+    let mut signals = properties.receive_properties_changed().await?;
+
+    while let Some(signal) = signals.next().await {
+        if signal
+            .args()
+            .is_ok_and(|args| args.interface_name.as_str() == PLAYER_INTERFACE)
+            && changes.send(name.clone()).await.is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+enum PlayerEvent {
+    Appeared(String),
+    Vanished(String),
+    Changed(String),
+}
+
+/// Follows every MPRIS player of the session. Sends the whole list every time one of them
+/// changes, the one that most recently started playing first.
+pub async fn track(output: &mut (impl Sink<Vec<Player>> + Unpin)) -> Result<(), Error> {
+    let connection = zbus::Connection::session().await?;
+    let dbus = DBusProxy::new(&connection).await?;
+
+    let (changes_tx, changes_rx) = smol::channel::unbounded();
+    let mut events = stream::select_all([
+        dbus.receive_name_owner_changed()
+            .await?
+            .filter_map(|change| async move {
+                let args = change.args().ok()?;
+                let name = args.name.as_str();
+                if !name.starts_with(BUS_PREFIX) {
+                    return None;
+                }
+
+                Some(if args.new_owner.is_some() {
+                    PlayerEvent::Appeared(name.to_string())
+                } else {
+                    PlayerEvent::Vanished(name.to_string())
+                })
+            })
+            .boxed(),
+        changes_rx.map(PlayerEvent::Changed).boxed(),
+    ]);
+
+    let mut players = Vec::<Player>::new();
+    // Dropping a task stops listening to the changes of its player.
+    let mut watches = HashMap::<String, smol::Task<()>>::new();
+
+    let initial = dbus
+        .list_names()
+        .await?
+        .into_iter()
+        .filter(|name| name.as_str().starts_with(BUS_PREFIX))
+        .map(|name| PlayerEvent::Appeared(name.as_str().to_string()))
+        .collect::<Vec<_>>();
+    let mut pending = initial.into_iter();
+
+    loop {
+        let event = match pending.next() {
+            Some(event) => event,
+            None => {
+                let _ = output.send(players.clone()).await;
+                match events.next().await {
+                    Some(event) => event,
+                    None => return Err(Error::Stopped("the media player watch")),
+                }
+            }
+        };
+
+        match event {
+            // Late changes of a player that is already gone.
+            PlayerEvent::Changed(name) if !watches.contains_key(&name) => {}
+            PlayerEvent::Appeared(name) | PlayerEvent::Changed(name) => {
+                // Players may take the name before they are ready to be read, they are read
+                // again on their first change.
+                let (watch_connection, changes) = (connection.clone(), changes_tx.clone());
+                watches.entry(name.clone()).or_insert_with(|| {
+                    let name = name.clone();
+                    smol::spawn(async move {
+                        let _ = watch_player(watch_connection, name, changes).await;
+                    })
+                });
+
+                let player = match player_proxy(&connection, &name).await {
+                    Ok(proxy) => fetch(&proxy, &name).await,
+                    Err(err) => Err(err),
+                };
+                let player = match player {
+                    Ok(player) => player,
+                    Err(err) => {
+                        tracing::warn!("Could not read media player {name}: {err}");
+                        continue;
+                    }
+                };
+
+                let position = players.iter().position(|p| p.name == name);
+                let was_playing =
+                    position.is_some_and(|i| players[i].status == PlaybackStatus::Playing);
+                if let Some(i) = position {
+                    players.remove(i);
+                }
+
+                if player.status == PlaybackStatus::Playing && !was_playing {
+                    players.insert(0, player);
+                } else {
+                    players.insert(position.unwrap_or(players.len()), player);
+                }
+            }
+            PlayerEvent::Vanished(name) => {
+                players.retain(|p| p.name != name);
+                watches.remove(&name);
+            }
+        }
+    }
+}
+
+async fn session_player(name: &str) -> Result<MprisPlayerProxy<'static>, Error> {
+    let connection = zbus::Connection::session().await?;
+    Ok(player_proxy(&connection, name).await?)
+}
+
+pub async fn play_pause(name: &str) -> Result<(), Error> {
+    Ok(session_player(name).await?.play_pause().await?)
+}
+
+pub async fn next(name: &str) -> Result<(), Error> {
+    Ok(session_player(name).await?.next().await?)
+}
+
+pub async fn previous(name: &str) -> Result<(), Error> {
+    Ok(session_player(name).await?.previous().await?)
+}
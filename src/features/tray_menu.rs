@@ -0,0 +1,197 @@
+use std::{collections::HashSet, time::Instant};
+
+use iced::{
+    Alignment::Center,
+    Element,
+    Length::Fill,
+    Padding, Task,
+    alignment::Horizontal,
+    border,
+    widget::{Column, button, column, container, mouse_area, opaque, row, space, text},
+};
+use iced_layershell::reexport::{
+    Anchor, KeyboardInteractivity, Layer, NewLayerShellSettings, OutputOption,
+};
+use lucide_icons::iced::{icon_check, icon_chevron_down, icon_chevron_right, icon_circle_dot};
+use wayland_client::protocol::wl_output::WlOutput;
+
+use crate::{
+    FeatureSelector, Message,
+    features::Feature,
+    styles,
+    tray::menu::{self, Menu, MenuItem, Toggle},
+};
+
+/// The menu of a tray item, opened below the bar on the side holding the tray.
+#[derive(Debug)]
+pub struct TrayMenu {
+    now: Instant,
+    output: String,
+    wloutput: WlOutput,
+    align: Horizontal,
+    menu: Menu,
+    /// Submenus unfolded below their parent item.
+    expanded: HashSet<i32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum TrayMenuMessage {
+    ToggleSubmenu(i32),
+    Clicked(i32),
+}
+
+impl TrayMenu {
+    pub fn new(
+        output: String,
+        wloutput: WlOutput,
+        align: Horizontal,
+        menu: Menu,
+        now: Instant,
+    ) -> Self {
+        Self {
+            now,
+            output,
+            wloutput,
+            align,
+            menu,
+            expanded: HashSet::new(),
+        }
+    }
+
+    fn items<'a>(&'a self, items: &'a [MenuItem], depth: u16) -> Column<'a, Message> {
+        Column::with_children(items.iter().map(|item| self.item(item, depth)))
+    }
+
+    fn item<'a>(&'a self, item: &'a MenuItem, depth: u16) -> Element<'a, Message> {
+        if item.separator {
+            let line = container(space())
+                .height(1)
+                .width(Fill)
+                .style(|theme| container::Style {
+                    background: Some(theme.palette().text.scale_alpha(0.2).into()),
+                    ..Default::default()
+                });
+
+            return container(line).padding([4, 12]).into();
+        }
+
+        let expanded = self.expanded.contains(&item.id);
+        let marker: Option<Element<'_, Message>> = match item.toggle {
+            Some(Toggle::Checkmark(true)) => Some(icon_check().size(16).into()),
+            Some(Toggle::Radio(true)) => Some(icon_circle_dot().size(16).into()),
+            Some(_) => Some(space().width(16).into()),
+            None => None,
+        };
+        let chevron: Option<Element<'_, Message>> = match (item.children.is_empty(), expanded) {
+            (true, _) => None,
+            (false, true) => Some(icon_chevron_down().size(16).into()),
+            (false, false) => Some(icon_chevron_right().size(16).into()),
+        };
+
+        let message = if item.children.is_empty() {
+            TrayMenuMessage::Clicked(item.id)
+        } else {
+            TrayMenuMessage::ToggleSubmenu(item.id)
+        };
+
+        let entry = button(
+            row![marker, text(&item.label).width(Fill), chevron]
+                .spacing(8)
+                .align_y(Center),
+        )
+        .width(Fill)
+        .padding(Padding::from([6, 12]).left(12. + 16. * f32::from(depth)))
+        .style(|theme, status| {
+            let palette = theme.palette();
+            let background = matches!(status, button::Status::Hovered | button::Status::Pressed)
+                .then(|| palette.text.scale_alpha(0.1).into());
+            let text_color = if status == button::Status::Disabled {
+                palette.text.scale_alpha(0.4)
+            } else {
+                palette.text
+            };
+
+            button::Style {
+                background,
+                text_color,
+                border: border::rounded(8),
+                ..Default::default()
+            }
+        })
+        .on_press_maybe(item.enabled.then_some(Message::TrayMenu(message)));
+
+        if expanded {
+            column![entry, self.items(&item.children, depth + 1)].into()
+        } else {
+            entry.into()
+        }
+    }
+}
+
+impl Feature for TrayMenu {
+    type InnerMessage = TrayMenuMessage;
+
+    fn layer_settings(&self) -> NewLayerShellSettings {
+        NewLayerShellSettings {
+            size: Some((0, 0)),
+            layer: Layer::Overlay,
+            anchor: Anchor::all(),
+            exclusive_zone: Some(-1),
+            namespace: Some("tray_menu".to_string()),
+            keyboard_interactivity: KeyboardInteractivity::None,
+            output_option: OutputOption::Output(self.wloutput.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn update(&mut self, message: TrayMenuMessage) -> Task<Message> {
+        match message {
+            TrayMenuMessage::ToggleSubmenu(id) => {
+                if !self.expanded.remove(&id) {
+                    self.expanded.insert(id);
+                }
+                Task::none()
+            }
+            TrayMenuMessage::Clicked(id) => {
+                let (item, path) = (self.menu.item.clone(), self.menu.path.clone());
+                let click = Task::future(async move {
+                    if let Err(err) = menu::clicked(&item, path, id).await {
+                        tracing::warn!(
+                            "Could not activate entry {id} of the menu of {item}: {err}"
+                        );
+                    }
+                });
+
+                Task::batch([
+                    click.discard(),
+                    Task::done(Message::Hide(FeatureSelector::TrayMenu)),
+                ])
+            }
+        }
+    }
+
+    fn view(&self) -> impl Into<Element<'_, Message>> {
+        let menu = container(self.items(&self.menu.items, 0).width(Fill))
+            .width(280)
+            .padding(8)
+            .style(|theme| styles::chord_style(theme).border(border::rounded(24)));
+
+        mouse_area(
+            container(opaque(menu))
+                .width(Fill)
+                .height(Fill)
+                .align_x(self.align)
+                // Right below the bar, lined up with its outer beads.
+                .padding(Padding::ZERO.top(72).left(24).right(24)),
+        )
+        .on_press(Message::Hide(FeatureSelector::TrayMenu))
+    }
+
+    fn output(&self) -> Option<&str> {
+        Some(&self.output)
+    }
+
+    fn set_now(&mut self, now: Instant) {
+        self.now = now;
+    }
+}
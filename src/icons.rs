@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex},
+};
+
+use iced::{
+    Element, Length,
+    widget::{image, svg},
+};
+
+/// Sizes looked up in the `hicolor` theme, from the sharpest to the blurriest.
+const SIZES: [&str; 8] = [
+    "scalable", "512x512", "256x256", "128x128", "64x64", "48x48", "32x32", "24x24",
+];
+const CATEGORIES: [&str; 3] = ["apps", "status", "devices"];
+
+static APP_ICONS: LazyLock<Mutex<HashMap<String, Option<Icon>>>> = LazyLock::new(Default::default);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Icon {
+    Raster(PathBuf),
+    Vector(PathBuf),
+}
+
+impl Icon {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        if !path.is_file() {
+            return None;
+        }
+
+        match path.extension()?.to_str()? {
+            "svg" => Some(Icon::Vector(path)),
+            "png" | "xpm" | "jpg" | "jpeg" => Some(Icon::Raster(path)),
+            _ => None,
+        }
+    }
+
+    pub fn view<'a, T: 'a>(&self, size: impl Into<Length> + Copy) -> Element<'a, T> {
+        self.view_faded(size, 1.)
+    }
+
+    /// The icon drawn with an `opacity` between `0` and `1`, for surfaces fading in and out.
+    pub fn view_faded<'a, T: 'a>(
+        &self,
+        size: impl Into<Length> + Copy,
+        opacity: f32,
+    ) -> Element<'a, T> {
+        match self {
+            Icon::Raster(path) => image(image::Handle::from_path(path))
+                .width(size)
+                .height(size)
+                .opacity(opacity)
+                .into(),
+            Icon::Vector(path) => svg(svg::Handle::from_path(path))
+                .width(size)
+                .height(size)
+                .opacity(opacity)
+                .into(),
+        }
+    }
+}
+
+/// `$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`, with the defaults of the base directory spec.
+fn data_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")));
+    let system = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+
+    home.into_iter()
+        .chain(system.split(':').map(PathBuf::from))
+        .collect()
+}
+
+/// Finds an icon by name in the `hicolor` theme and `/usr/share/pixmaps`, or by path when the
+/// name is an absolute one, as allowed by desktop entries and notifications.
+pub fn lookup(name: &str) -> Option<Icon> {
+    let name = name.strip_prefix("file://").unwrap_or(name);
+    if Path::new(name).is_absolute() {
+        return Icon::from_path(PathBuf::from(name));
+    }
+
+    let themes = data_dirs().into_iter().map(|d| d.join("icons/hicolor"));
+    let candidates = themes.flat_map(|theme| {
+        SIZES.iter().flat_map(move |size| {
+            let theme = theme.clone();
+            CATEGORIES
+                .iter()
+                .map(move |category| theme.join(size).join(category))
+        })
+    });
+
+    candidates
+        .chain(["/usr/share/pixmaps".into()])
+        .find_map(|dir| lookup_in(&dir, name))
+}
+
+/// Finds an icon by name directly inside `dir`, preferring SVG.
+pub fn lookup_in(dir: &Path, name: &str) -> Option<Icon> {
+    ["svg", "png"]
+        .into_iter()
+        .find_map(|ext| Icon::from_path(dir.join(format!("{name}.{ext}"))))
+}
+
+/// The icon of an application given its Wayland app id or X11 class, from its desktop entry
+/// when one matches, otherwise from an icon of the same name. Results are cached.
+pub fn for_app(class: &str) -> Option<Icon> {
+    if class.is_empty() {
+        return None;
+    }
+
+    if let Some(icon) = APP_ICONS.lock().ok()?.get(class) {
+        return icon.clone();
+    }
+
+    let icon = desktop_entry_icon(class)
+        .and_then(|name| lookup(&name))
+        .or_else(|| lookup(class))
+        .or_else(|| lookup(&class.to_lowercase()));
+    APP_ICONS
+        .lock()
+        .ok()?
+        .insert(class.to_string(), icon.clone());
+
+    icon
+}
+
+/// The `Icon` of the desktop entry named after the class, or declaring it as `StartupWMClass`.
+fn desktop_entry_icon(class: &str) -> Option<String> {
+    let entries = data_dirs()
+        .into_iter()
+        .filter_map(|d| fs::read_dir(d.join("applications")).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "desktop"));
+
+    let mut by_wm_class = None;
+    for path in entries {
+        let Ok(contents) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let field = |key: &str| {
+            contents
+                .lines()
+                .skip_while(|l| l.trim() != "[Desktop Entry]")
+                .take_while(|l| !l.starts_with('[') || l.trim() == "[Desktop Entry]")
+                .find_map(|l| l.strip_prefix(key)?.trim_start().strip_prefix('='))
+                .map(|v| v.trim().to_string())
+        };
+
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default();
+        if stem.eq_ignore_ascii_case(class) {
+            return field("Icon");
+        }
+        if by_wm_class.is_none() && field("StartupWMClass").is_some_and(|c| c == class) {
+            by_wm_class = field("Icon");
+        }
+    }
+
+    by_wm_class
+}
@@ -0,0 +1,285 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use iced::{
+    Subscription,
+    futures::{SinkExt, channel::mpsc},
+    stream,
+};
+use serde::Serialize;
+use smol::{
+    channel::{Sender, bounded},
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::unix::{UnixListener, UnixStream},
+    stream::StreamExt,
+};
+
+use crate::{
+    FeatureSelector, Message, brightness::BrightnessChange, config::ThemeVariant,
+    features::osd::OsdRequest,
+};
+
+const USAGE: &str = "\
+usage: waybracelet msg <command>
+
+commands:
+  open <power-menu|notifications>
+  hide <power-menu|notifications>
+  toggle <power-menu|notifications>
+  show-osd <volume|brightness> <0-100>
+  show-osd level <0-100> [icon]
+  show-osd toggle <on|off> <label> [icon]
+  show-osd text <text> [icon]
+  brightness <0-100|+N|-N>
+  theme <toggle|dark|light>
+  dismiss-notifications
+  reload
+  status";
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Open(FeatureSelector),
+    Hide(FeatureSelector),
+    Toggle(FeatureSelector),
+    ShowOsd(OsdRequest),
+    Brightness(BrightnessChange),
+    ToggleTheme,
+    SetTheme(ThemeVariant),
+    DismissNotifications,
+    Reload,
+    Status,
+}
+
+impl Command {
+    pub fn parse(args: &[impl AsRef<str>]) -> Result<Self, String> {
+        let args = args.iter().map(|a| a.as_ref()).collect::<Vec<&str>>();
+
+        let toggleable = |feature: &str| match feature.parse()? {
+            f @ (FeatureSelector::PowerMenu | FeatureSelector::Notifications) => Ok(f),
+            _ => Err(format!("`{feature}` cannot be opened or hidden")),
+        };
+
+        let percentage = |value: &str| {
+            value
+                .parse::<u8>()
+                .ok()
+                .filter(|v| *v <= 100)
+                .ok_or_else(|| format!("`{value}` is not a percentage between 0 and 100"))
+        };
+
+        let level = |percentage: u8| f32::from(percentage) / 100.;
+        let named = |icon: &[&str]| icon.first().map(|i| i.to_string());
+
+        match args.as_slice() {
+            ["open", feature] => toggleable(feature).map(Command::Open),
+            ["hide", feature] => toggleable(feature).map(Command::Hide),
+            ["toggle", feature] => toggleable(feature).map(Command::Toggle),
+            ["show-osd", "volume", value] => {
+                percentage(value).map(|v| Command::ShowOsd(OsdRequest::Volume(level(v))))
+            }
+            ["show-osd", "brightness", value] => {
+                percentage(value).map(|v| Command::ShowOsd(OsdRequest::Brightness(level(v))))
+            }
+            ["show-osd", "level", value, icon @ ..] if icon.len() <= 1 => percentage(value)
+                .map(|v| Command::ShowOsd(OsdRequest::Level(level(v), named(icon)))),
+            ["show-osd", "toggle", state, label, icon @ ..] if icon.len() <= 1 => {
+                let on = match *state {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("`{state}` is neither `on` nor `off`")),
+                };
+
+                Ok(Command::ShowOsd(OsdRequest::Toggle {
+                    on,
+                    label: label.to_string(),
+                    icon: named(icon),
+                }))
+            }
+            ["show-osd", "text", text, icon @ ..] if icon.len() <= 1 => Ok(Command::ShowOsd(
+                OsdRequest::Text(text.to_string(), named(icon)),
+            )),
+            ["brightness", value] => {
+                let step = |value: &str| percentage(value).map(level);
+                match (value.strip_prefix('+'), value.strip_prefix('-')) {
+                    (Some(up), _) => step(up).map(BrightnessChange::By),
+                    (_, Some(down)) => step(down).map(|s| BrightnessChange::By(-s)),
+                    _ => step(value).map(BrightnessChange::To),
+                }
+                .map(Command::Brightness)
+            }
+            ["theme", "toggle"] => Ok(Command::ToggleTheme),
+            ["theme", "dark"] => Ok(Command::SetTheme(ThemeVariant::Dark)),
+            ["theme", "light"] => Ok(Command::SetTheme(ThemeVariant::Light)),
+            ["dismiss-notifications"] => Ok(Command::DismissNotifications),
+            ["reload"] => Ok(Command::Reload),
+            ["status"] => Ok(Command::Status),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Reply {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
+}
+
+impl Reply {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            data: None,
+        }
+    }
+
+    pub fn data(data: serde_json::Value) -> Self {
+        Self {
+            data: Some(data),
+            ..Self::ok()
+        }
+    }
+
+    pub fn error(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(error.into()),
+            data: None,
+        }
+    }
+}
+
+/// A command received on the control socket, waiting for the daemon to answer it.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub command: Command,
+    reply: Sender<Reply>,
+}
+
+impl Request {
+    pub fn reply(&self, reply: Reply) {
+        let _ = self.reply.try_send(reply);
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/waybracelet.sock`
+pub fn socket_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .filter(|d| !d.is_empty())
+        .map(|d| PathBuf::from(d).join("waybracelet.sock"))
+}
+
+pub fn subscription() -> Subscription<Message> {
+    Subscription::run(|| {
+        stream::channel(8, async |output| {
+            let Some(path) = socket_path() else {
+                tracing::warn!("XDG_RUNTIME_DIR is not set, the control socket is disabled");
+                return;
+            };
+
+            let listener = match bind(&path) {
+                Ok(listener) => listener,
+                Err(err) => {
+                    tracing::error!("Could not listen on {}: {err}", path.display());
+                    return;
+                }
+            };
+
+            let mut incoming = listener.incoming();
+            while let Some(stream) = incoming.next().await {
+                match stream {
+                    Ok(stream) => smol::spawn(serve(stream, output.clone())).detach(),
+                    Err(err) => tracing::warn!("Could not accept an IPC connection: {err}"),
+                }
+            }
+        })
+    })
+}
+
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "another instance is already listening",
+        ));
+    }
+
+    // Left behind by a daemon that did not exit cleanly.
+    let _ = std::fs::remove_file(path);
+    UnixListener::bind(path)
+}
+
+async fn serve(mut stream: UnixStream, mut output: mpsc::Sender<Message>) {
+    let mut line = String::new();
+    if let Err(err) = BufReader::new(stream.clone()).read_line(&mut line).await {
+        tracing::warn!("Could not read an IPC request: {err}");
+        return;
+    }
+
+    let command = serde_json::from_str::<Vec<String>>(&line)
+        .map_err(|err| err.to_string())
+        .and_then(|args| Command::parse(&args));
+
+    let reply = match command {
+        Ok(command) => {
+            let (reply, answer) = bounded(1);
+            let _ = output.send(Message::Ipc(Request { command, reply })).await;
+            answer
+                .recv()
+                .await
+                .unwrap_or_else(|_| Reply::error("the daemon dropped the request"))
+        }
+        Err(err) => Reply::error(err),
+    };
+
+    let mut reply = serde_json::to_string(&reply).unwrap_or_default();
+    reply.push('\n');
+    if let Err(err) = stream.write_all(reply.as_bytes()).await {
+        tracing::warn!("Could not answer an IPC request: {err}");
+    }
+}
+
+/// Entry point of `waybracelet msg`. Sends the command to the running daemon, prints its JSON
+/// reply and returns the process exit code.
+pub fn client(args: &[String]) -> i32 {
+    if let Err(err) = Command::parse(args) {
+        eprintln!("{err}");
+        return 2;
+    }
+
+    let Some(path) = socket_path() else {
+        eprintln!("waybracelet: XDG_RUNTIME_DIR is not set");
+        return 1;
+    };
+
+    match send(&path, args) {
+        Ok(reply) => {
+            print!("{reply}");
+            let ok = serde_json::from_str::<serde_json::Value>(&reply)
+                .is_ok_and(|r| r["ok"] == true);
+            if ok { 0 } else { 1 }
+        }
+        Err(err) => {
+            eprintln!("waybracelet: could not reach the daemon at {}: {err}", path.display());
+            1
+        }
+    }
+}
+
+fn send(path: &Path, args: &[String]) -> io::Result<String> {
+    let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+
+    let mut request = serde_json::to_string(args)?;
+    request.push('\n');
+    stream.write_all(request.as_bytes())?;
+
+    let mut reply = String::new();
+    io::BufReader::new(stream).read_line(&mut reply)?;
+
+    Ok(reply)
+}
@@ -1,11 +1,11 @@
 #![warn(unused_extern_crates)]
 #![allow(mismatched_lifetime_syntaxes)]
 
-use std::{fs::File, iter};
+use std::{fs::File, iter, str::FromStr};
 
-use hyprland::{data::Monitors, shared::HyprData};
 use iced::{
-    Color, Element, Size, Subscription, Task,
+    Color, Element, Size, Subscription, Task, Theme,
+    alignment::Horizontal,
     theme::Style,
     time::Instant,
     widget::{container, space},
@@ -17,52 +17,99 @@ use iced_layershell::{
     to_layer_message,
 };
 use iced_wayland_subscriber::{OutputInfo, WaylandEvent};
+use smol::channel::Sender;
 use tracing::Level;
 use tracing_panic::panic_hook;
 use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, util::SubscriberInitExt};
 use wayland_client::Connection;
 
 use crate::{
+    config::{Config, ThemeSetting, ThemeVariant},
     features::{
         Feature,
-        notifications::{self, Notifications},
+        notifications::{self, DbusEvents, Notifications, NotificationsMessage},
+        osd::{Osd, OsdMessage},
         power_menu::PowerMenu,
-        status_bar::StatusBar,
-        volume_osd::VolumeOSD,
+        status_bar::{self, Module, Player, StatusBar, StatusBarMessage},
+        tray_menu::{TrayMenu, TrayMenuMessage},
     },
-    styles::dark_theme,
-    windows::Window,
+    ipc::{Command, Reply},
+    sink::SinkVolume,
+    styles::{dark_theme, light_theme},
+    tray::TrayItem,
+    windows::{Mirrored, Window},
 };
 
+mod brightness;
 mod components;
+mod compositor;
+mod config;
+mod error;
 mod features;
+mod icons;
+mod ipc;
+mod sink;
 mod styles;
+mod theme;
+mod tray;
+mod visualizer;
 mod windows;
 
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).is_some_and(|a| a == "msg") {
+        std::process::exit(ipc::client(&args[2..]));
+    }
+
     std::panic::set_hook(Box::new(panic_hook));
 
-    let file = File::create("/home/dcxo/debug.wb.log").unwrap();
-    let log = fmt::layer()
-        .with_writer(file)
-        .with_filter(EnvFilter::from("info,iced_layershell=warn,calloop=warn"));
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("waybracelet: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let (log, log_error) = match config.log_file.as_ref().map(File::create).transpose() {
+        Ok(file) => (
+            file.map(|file| {
+                fmt::layer()
+                    .with_writer(file)
+                    .with_filter(EnvFilter::from("info,iced_layershell=warn,calloop=warn"))
+            }),
+            None,
+        ),
+        Err(err) => (None, Some(err)),
+    };
 
     tracing_subscriber::registry()
         .with(fmt::layer().with_filter(EnvFilter::from_default_env()))
         .with(log)
         .init();
 
-    let conn = Connection::connect_to_env().unwrap();
+    if let Some(err) = log_error {
+        tracing::warn!("Could not create the log file, logging to stderr only: {err}");
+    }
+
+    // Without a Wayland connection there is nothing to draw on.
+    let conn = match Connection::connect_to_env() {
+        Ok(conn) => conn,
+        Err(err) => {
+            eprintln!("waybracelet: could not connect to the Wayland compositor: {err}");
+            std::process::exit(1);
+        }
+    };
     let conn2 = conn.clone();
 
-    iced_layershell::daemon(
-        move || Daemon::new(conn.clone()),
+    let result = iced_layershell::daemon(
+        move || Daemon::new(conn.clone(), config.clone()),
         || "WayBracelet".into(),
         Daemon::update,
         Daemon::view,
     )
     .subscription(Daemon::subscriptions)
-    .theme(|_: &Daemon, _| dark_theme())
+    .theme(|daemon: &Daemon, _| daemon.theme())
     .style(|_, theme| Style {
         background_color: Color::TRANSPARENT,
         text_color: theme.palette().text,
@@ -76,8 +123,12 @@ fn main() {
         },
         ..Default::default()
     })
-    .run()
-    .unwrap()
+    .run();
+
+    if let Err(err) = result {
+        tracing::error!("{err}");
+        std::process::exit(1);
+    }
 }
 
 #[to_layer_message(multi)]
@@ -85,8 +136,13 @@ fn main() {
 pub enum Message {
     StatusBar(features::status_bar::StatusBarMessage),
     PowerMenu(features::power_menu::PowerMenuMessage),
-    VolumeOSD(features::volume_osd::VolumeOsdMessage),
+    Osd(features::osd::OsdMessage),
     Notifications(features::notifications::NotificationsMessage),
+    TrayMenu(TrayMenuMessage),
+
+    /// Opens the menu of a tray item on the output of the bar it was asked from, on the side
+    /// of the bar holding the tray.
+    OpenTrayMenu(String, Horizontal, tray::menu::Menu),
 
     Open(FeatureSelector),
     Hide(FeatureSelector),
@@ -94,31 +150,67 @@ pub enum Message {
     ChangeSize(FeatureSelector, Size),
 
     DisplayInserted(OutputInfo),
+    DisplayChanged(OutputInfo),
+    DisplayRemoved(OutputInfo),
+    ReloadConfig,
+    Ipc(ipc::Request),
     Animation,
     ChangeTheme,
+    SetTheme(ThemeVariant),
 }
 
 #[derive(Debug, Clone)]
 pub enum FeatureSelector {
     StatusBar,
     PowerMenu,
-    VolumeOSD,
+    Osd,
     Notifications,
+    TrayMenu,
+}
+
+impl FromStr for FeatureSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "status-bar" => Ok(FeatureSelector::StatusBar),
+            "power-menu" => Ok(FeatureSelector::PowerMenu),
+            "osd" => Ok(FeatureSelector::Osd),
+            "notifications" => Ok(FeatureSelector::Notifications),
+            _ => Err(format!("unknown feature `{s}`")),
+        }
+    }
 }
 
 struct Daemon {
     statuses_bar: Vec<Window<StatusBar>>,
-    volume_osd: Window<VolumeOSD>,
+    osd: Mirrored<Osd>,
     power_menu: Option<Window<PowerMenu>>,
     notifications: Option<Window<Notifications>>,
+    /// Signals of the notifications server, kept for every notifications window to come.
+    notifications_events: Option<Sender<DbusEvents>>,
+    tray_menu: Option<Window<TrayMenu>>,
+    /// Last items of the tray, for the status bars opened after they were listed.
+    tray: Vec<TrayItem>,
+    /// Last state of the players, for the same reason.
+    players: Vec<Player>,
+    /// Last volume of the default sink, for the same reason.
+    sink: Option<SinkVolume>,
     connection: Connection,
+    config: Config,
+    theme: ThemeVariant,
     now: Instant,
 }
 
 impl Daemon {
-    fn new(connection: Connection) -> (Self, Task<Message>) {
+    fn new(connection: Connection, config: Config) -> (Self, Task<Message>) {
         let now = Instant::now();
-        let (volume_osd, volume_open_task) = VolumeOSD::new(now).open();
+        let (osd, osd_open_task) = Mirrored::open(Osd::new(
+            config.osd.clone(),
+            config.brightness.clone(),
+            config.compositor,
+            now,
+        ));
 
         // let (statuses_bar, mut open_tasks) = Monitors::get()
         //     .unwrap()
@@ -131,12 +223,19 @@ impl Daemon {
             Self {
                 connection,
                 statuses_bar: vec![],
-                volume_osd,
+                osd,
                 power_menu: None,
                 notifications: None,
+                notifications_events: None,
+                tray_menu: None,
+                tray: vec![],
+                players: vec![],
+                sink: None,
+                theme: config.theme.initial_variant(),
+                config,
                 now,
             },
-            volume_open_task,
+            osd_open_task,
         )
     }
 
@@ -144,12 +243,26 @@ impl Daemon {
         self.set_now();
 
         match message {
-            Message::StatusBar(message) => self
-                .statuses_bar
-                .iter_mut()
-                .map(move |sb| sb.update(message.clone()))
-                .fold(Task::none(), |mt, t| mt.chain(t)),
-            Message::VolumeOSD(message) => self.volume_osd.update(message),
+            Message::StatusBar(message) => {
+                match &message {
+                    StatusBarMessage::TrayItems(items) => self.tray = items.clone(),
+                    StatusBarMessage::Players(players) => self.players = players.clone(),
+                    StatusBarMessage::Sink(sink) => self.sink = Some(*sink),
+                    _ => {}
+                }
+
+                self.statuses_bar
+                    .iter_mut()
+                    .map(move |sb| sb.update(message.clone()))
+                    .fold(Task::none(), |mt, t| mt.chain(t))
+            }
+            Message::Osd(message) => self.osd.update(message),
+            Message::Notifications(NotificationsMessage::DbusInterfaceReady(sender)) => {
+                self.notifications_events = Some(sender.clone());
+                self.notifications.as_mut().map_or(Task::none(), |ns| {
+                    ns.update(NotificationsMessage::DbusInterfaceReady(sender))
+                })
+            }
             Message::Notifications(message) => {
                 if let Some(ns) = self.notifications.as_mut() {
                     ns.update(message)
@@ -162,6 +275,33 @@ impl Daemon {
                 .as_mut()
                 .map(|pm| pm.update(message))
                 .unwrap_or(Task::none()),
+            Message::TrayMenu(message) => self
+                .tray_menu
+                .as_mut()
+                .map(|tm| tm.update(message))
+                .unwrap_or(Task::none()),
+
+            Message::OpenTrayMenu(output, side, menu) => {
+                let Some(wloutput) = self
+                    .statuses_bar
+                    .iter()
+                    .find(|sb| sb.output == output)
+                    .map(|sb| sb.wloutput.clone())
+                else {
+                    return Task::none();
+                };
+
+                // A single menu is open at a time.
+                let close_task = self
+                    .tray_menu
+                    .take()
+                    .map_or(Task::none(), |tm| Task::done(Message::RemoveWindow(tm.id)));
+                let (window, open_task) =
+                    TrayMenu::new(output, wloutput, side, menu, self.now).open();
+                self.tray_menu.replace(window);
+
+                close_task.chain(open_task)
+            }
 
             Message::Open(feature) => match feature {
                 FeatureSelector::PowerMenu if self.power_menu.is_none() => {
@@ -171,8 +311,14 @@ impl Daemon {
                     open_task
                 }
                 FeatureSelector::Notifications if self.notifications.is_none() => {
-                    let (window, open_task) =
-                        Notifications::new(Default::default(), self.now).open();
+                    let (window, open_task) = Notifications::new(
+                        Default::default(),
+                        self.config.notifications.clone(),
+                        self.notifications_events.clone(),
+                        self.output_names(),
+                        self.now,
+                    )
+                    .open();
                     self.notifications.replace(window);
 
                     open_task
@@ -185,6 +331,7 @@ impl Daemon {
                 let id = match feature {
                     FeatureSelector::PowerMenu => self.power_menu.as_ref().map(|pm| pm.id),
                     FeatureSelector::Notifications => self.notifications.as_ref().map(|ns| ns.id),
+                    FeatureSelector::TrayMenu => self.tray_menu.as_ref().map(|tm| tm.id),
                     _ => unreachable!(),
                 };
                 if let Some(id) = id {
@@ -202,6 +349,9 @@ impl Daemon {
                     FeatureSelector::PowerMenu => {
                         self.power_menu.take();
                     }
+                    FeatureSelector::TrayMenu => {
+                        self.tray_menu.take();
+                    }
                     _ => unreachable!(),
                 };
                 Task::none()
@@ -210,8 +360,9 @@ impl Daemon {
             Message::ChangeSize(f, s) => {
                 let Some(id) = (match f {
                     FeatureSelector::Notifications => self.notifications.as_ref().map(|f| f.id),
+                    FeatureSelector::TrayMenu => self.tray_menu.as_ref().map(|f| f.id),
                     FeatureSelector::PowerMenu => todo!(),
-                    FeatureSelector::VolumeOSD => todo!(),
+                    FeatureSelector::Osd => todo!(),
                     FeatureSelector::StatusBar => todo!(),
                 }) else {
                     return Task::none();
@@ -224,26 +375,89 @@ impl Daemon {
             }
 
             Message::DisplayInserted(info) => {
-                let m = Monitors::get()
-                    .unwrap()
-                    .iter()
-                    .find(|m| m.name == info.name)
-                    .map(|m| m.active_workspace.id);
-
-                let (window, task) =
-                    StatusBar::new(info.name, info.wl_output, m.unwrap_or(1), self.now).open();
+                let mut status_bar = StatusBar::new(
+                    info.name,
+                    info.wl_output,
+                    self.config.compositor,
+                    self.config.status_bar.clone(),
+                    self.now,
+                );
+                // The tray, media and volume subscriptions are shared, they do not report the
+                // current state again for this bar.
+                status_bar.tray = self.tray.clone();
+                status_bar.players = self.players.clone();
+                status_bar.sink = self.sink;
+                let (window, task) = status_bar.open();
 
                 let current_status_bar = self
                     .statuses_bar
                     .iter_mut()
                     .find(|sb| sb.output == window.output);
 
-                if let Some(current_status_bar) = current_status_bar {
-                    let _ = std::mem::replace(current_status_bar, window);
+                let remove_task = if let Some(current_status_bar) = current_status_bar {
+                    let old = std::mem::replace(current_status_bar, window);
+                    Task::done(Message::RemoveWindow(old.id))
                 } else {
                     self.statuses_bar.push(window);
+                    Task::none()
+                };
+
+                Task::batch([remove_task, task, self.retarget_outputs(None)])
+            }
+
+            Message::DisplayChanged(info) => {
+                let Some(status_bar) = self
+                    .statuses_bar
+                    .iter_mut()
+                    .find(|sb| sb.wloutput == info.wl_output)
+                else {
+                    return Task::none();
+                };
+
+                status_bar.output = info.name;
+                Task::batch([status_bar.reopen(), self.retarget_outputs(None)])
+            }
+
+            Message::DisplayRemoved(info) => {
+                let (removed, kept) = std::mem::take(&mut self.statuses_bar)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|sb| sb.wloutput == info.wl_output);
+                self.statuses_bar = kept;
+
+                let tray_menu = self
+                    .tray_menu
+                    .take_if(|tm| tm.output().is_some_and(|o| o == info.name));
+
+                Task::batch(
+                    removed
+                        .into_iter()
+                        .map(|sb| sb.id)
+                        .chain(tray_menu.map(|tm| tm.id))
+                        .map(|id| Task::done(Message::RemoveWindow(id)))
+                        .chain(iter::once(self.retarget_outputs(Some(info.name.as_str())))),
+                )
+            }
+
+            Message::ReloadConfig => match Config::load() {
+                Ok(config) => self.apply_config(config),
+                Err(err) => {
+                    tracing::error!("Keeping the current configuration: {err}");
+                    Task::none()
                 }
+            },
+
+            Message::ChangeTheme => {
+                self.theme = self.theme.toggled();
+                Task::none()
+            }
+            Message::SetTheme(theme) => {
+                self.theme = theme;
+                Task::none()
+            }
 
+            Message::Ipc(request) => {
+                let (reply, task) = self.handle_command(request.command.clone());
+                request.reply(reply);
                 task
             }
 
@@ -251,15 +465,122 @@ impl Daemon {
         }
     }
 
+    fn handle_command(&mut self, command: Command) -> (Reply, Task<Message>) {
+        match command {
+            Command::Open(feature) => (Reply::ok(), self.update(Message::Open(feature))),
+            Command::Hide(feature) => (Reply::ok(), self.update(Message::Hide(feature))),
+            Command::Toggle(feature) => {
+                let is_open = match feature {
+                    FeatureSelector::PowerMenu => self.power_menu.is_some(),
+                    FeatureSelector::Notifications => self.notifications.is_some(),
+                    _ => false,
+                };
+                let message = if is_open {
+                    Message::Hide(feature)
+                } else {
+                    Message::Open(feature)
+                };
+
+                (Reply::ok(), self.update(message))
+            }
+            Command::ShowOsd(request) => (Reply::ok(), self.osd.update(OsdMessage::Show(request))),
+            Command::Brightness(change) => (
+                Reply::ok(),
+                self.osd.update(OsdMessage::ChangeBrightness(change)),
+            ),
+            Command::ToggleTheme => (Reply::ok(), self.update(Message::ChangeTheme)),
+            Command::SetTheme(theme) => (Reply::ok(), self.update(Message::SetTheme(theme))),
+            Command::DismissNotifications => (
+                Reply::ok(),
+                self.notifications
+                    .as_mut()
+                    .map(|ns| ns.update(NotificationsMessage::CloseAll))
+                    .unwrap_or(Task::none()),
+            ),
+            Command::Reload => match Config::load() {
+                Ok(config) => (Reply::ok(), self.apply_config(config)),
+                Err(err) => (Reply::error(err.to_string()), Task::none()),
+            },
+            Command::Status => (
+                Reply::data(serde_json::json!({
+                    "status_bars": self.output_names(),
+                    "power_menu": self.power_menu.is_some(),
+                    "theme": match self.theme {
+                        ThemeVariant::Dark => "dark",
+                        ThemeVariant::Light => "light",
+                    },
+                    "notifications": self
+                        .notifications
+                        .as_ref()
+                        .map_or(0, |ns| ns.ids().count()),
+                })),
+                Task::none(),
+            ),
+        }
+    }
+
+    fn output_names(&self) -> Vec<String> {
+        self.statuses_bar
+            .iter()
+            .map(|sb| sb.output.clone())
+            .collect()
+    }
+
+    /// Moves the surfaces that are not tied to a status bar when their output comes back or
+    /// goes away.
+    fn retarget_outputs(&mut self, removed: Option<&str>) -> Task<Message> {
+        let outputs = self.output_names();
+
+        Task::batch(
+            iter::once(self.osd.retarget(&outputs, removed)).chain(
+                self.notifications
+                    .as_mut()
+                    .map(|ns| ns.retarget(&outputs, removed)),
+            ),
+        )
+    }
+
+    fn apply_config(&mut self, config: Config) -> Task<Message> {
+        if config == self.config {
+            return Task::none();
+        }
+
+        tracing::info!("Applying the reloaded configuration");
+        if config.theme != self.config.theme {
+            self.theme = config.theme.initial_variant();
+        }
+        self.config = config;
+        let config = &self.config;
+
+        Task::batch(
+            self.statuses_bar
+                .iter_mut()
+                .map(|sb| sb.reconfigure(config))
+                .chain(iter::once(self.osd.reconfigure(config)))
+                .chain(self.notifications.as_mut().map(|ns| ns.reconfigure(config)))
+                .chain(self.power_menu.as_mut().map(|pm| pm.reconfigure(config)))
+                .chain(self.tray_menu.as_mut().map(|tm| tm.reconfigure(config))),
+        )
+    }
+
+    fn theme(&self) -> Option<Theme> {
+        match self.theme {
+            ThemeVariant::Dark => dark_theme(),
+            ThemeVariant::Light => light_theme(),
+        }
+    }
+
     fn view(&self, window_id: window::Id) -> Element<'_, Message> {
         if let Some(window) = self.statuses_bar.iter().find(|sb| sb.id == window_id) {
             window.view().into()
         } else if let Some(window) = self.notifications.as_ref().filter(|ns| window_id == ns.id) {
             window.view().into()
-        } else if window_id == self.volume_osd.id {
-            self.volume_osd.view().into()
+        } else if self.osd.contains(window_id) {
+            self.osd.view().into()
         } else if let Some(window) = self.power_menu.as_ref().filter(|pm| pm.id == window_id) {
             window.view().into()
+        } else if let Some(window) = self.tray_menu.as_ref().filter(|tm| tm.id == window_id) {
+            window.view().into()
         } else {
             container(space()).into()
         }
@@ -272,6 +593,21 @@ impl Daemon {
             Subscription::none()
         };
 
+        // A single analysis feeds the waves of every bar, as long as one of them draws them. It
+        // flattens them and idles by itself while the input is silent.
+        let visualizer = if self.statuses_bar.iter().any(|sb| sb.shows(Module::Cava)) {
+            status_bar::visualizer_subscription(self.config.status_bar.visualizer.clone())
+                .map(Message::StatusBar)
+        } else {
+            Subscription::none()
+        };
+
+        let follow_portal = if self.config.theme == ThemeSetting::System {
+            theme::portal_subscription()
+        } else {
+            Subscription::none()
+        };
+
         Subscription::batch(
             self.statuses_bar
                 .iter()
@@ -281,16 +617,23 @@ impl Daemon {
                 .chain(iter::once(
                     notifications::subscriptions::notifications_subscription(),
                 ))
-                .chain(iter::once(self.volume_osd.subscriptions()))
+                .chain(iter::once(self.osd.subscriptions()))
                 .chain([
                     frames,
-                    iced_wayland_subscriber::listen(self.connection.clone())
-                        .filter_map(|event| match event {
-                            WaylandEvent::OutputInsert(oi) => Some(oi),
+                    visualizer,
+                    config::watch_subscription(),
+                    ipc::subscription(),
+                    theme::signal_subscription(),
+                    follow_portal,
+                    iced_wayland_subscriber::listen(self.connection.clone()).filter_map(
+                        |event| match event {
+                            WaylandEvent::OutputInsert(oi) => Some(Message::DisplayInserted(oi)),
+                            WaylandEvent::OutputChanged(oi) => Some(Message::DisplayChanged(oi)),
+                            WaylandEvent::OutputRemoved(oi) => Some(Message::DisplayRemoved(oi)),
                             _ => None,
-                        })
-                        .map(Message::DisplayInserted),
-                ]), // .chain([frames, change_theme_subscription()]),
+                        },
+                    ),
+                ]),
         )
     }
 
@@ -299,8 +642,9 @@ impl Daemon {
             .as_ref()
             .is_some_and(|ns| ns.is_animating())
             || self.power_menu.as_ref().is_some_and(|pm| pm.is_animating())
+            || self.tray_menu.as_ref().is_some_and(|tm| tm.is_animating())
             || self.statuses_bar.iter().any(|sb| sb.is_animating())
-            || self.volume_osd.is_animating()
+            || self.osd.is_animating()
     }
 
     fn set_now(&mut self) {
@@ -308,7 +652,7 @@ impl Daemon {
         self.statuses_bar
             .iter_mut()
             .for_each(|sb| sb.set_now(self.now));
-        self.volume_osd.set_now(self.now);
+        self.osd.set_now(self.now);
         self.notifications
             .as_mut()
             .iter_mut()
@@ -318,12 +662,9 @@ impl Daemon {
             .as_mut()
             .iter_mut()
             .for_each(|pm| pm.set_now(self.now));
+        self.tray_menu
+            .as_mut()
+            .iter_mut()
+            .for_each(|tm| tm.set_now(self.now));
     }
 }
-
-// fn change_theme_subscription() -> Subscription<Message> {
-//     Subscription::run(|| {
-//         SignalStream::new(signal(SignalKind::user_defined1()).unwrap())
-//             .map(|_| Message::ChangeTheme)
-//     })
-// }
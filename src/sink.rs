@@ -0,0 +1,176 @@
+use std::process::Stdio;
+
+use iced::futures::{Sink, SinkExt};
+use smol::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    stream::StreamExt,
+};
+
+use crate::error::Error;
+
+/// Volume added by a step of the mouse wheel.
+pub const VOLUME_STEP: f32 = 0.05;
+
+/// Level and mute state of the default sink, `1` being 100%.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SinkVolume {
+    pub volume: f32,
+    pub muted: bool,
+}
+
+/// `pactl` with its output untranslated, for it to be parsed.
+fn pactl() -> Command {
+    let mut command = Command::new("pactl");
+    command.env("LC_ALL", "C");
+    command
+}
+
+async fn query(args: &[&str]) -> Result<String, Error> {
+    let output = pactl()
+        .args(args)
+        .output()
+        .await
+        .map_err(|err| Error::Io("could not run pactl", err))?;
+
+    if !output.status.success() {
+        return Err(Error::Failed(
+            "pactl failed",
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Reads the default sink. Its volume is the average of its channels.
+pub async fn default_sink() -> Result<SinkVolume, Error> {
+    let volume = query(&["get-sink-volume", "@DEFAULT_SINK@"]).await?;
+    let mute = query(&["get-sink-mute", "@DEFAULT_SINK@"]).await?;
+
+    // Volume: front-left: 32768 /  50% / -18.06 dB,   front-right: 32768 /  50% / -18.06 dB
+    let levels = volume
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter_map(|part| part.trim().strip_suffix('%')?.trim().parse::<f32>().ok())
+        .collect::<Vec<_>>();
+    if levels.is_empty() {
+        return Err(Error::Failed(
+            "could not read the volume from pactl",
+            volume.trim().to_string(),
+        ));
+    }
+
+    let muted = match mute.trim() {
+        "Mute: yes" => true,
+        "Mute: no" => false,
+        other => {
+            return Err(Error::Failed(
+                "could not read the mute state from pactl",
+                other.to_string(),
+            ));
+        }
+    };
+
+    Ok(SinkVolume {
+        volume: levels.iter().sum::<f32>() / levels.len() as f32 / 100.,
+        muted,
+    })
+}
+
+/// Sends the default sink once subscribed to its changes, and then every time it changes.
+pub async fn follow(output: &mut (impl Sink<SinkVolume> + Unpin)) -> Result<(), Error> {
+    let mut child = pactl()
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| Error::Io("could not run pactl subscribe", err))?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or(Error::Stopped("pactl subscribe"))?;
+    let mut events = BufReader::new(stdout).lines();
+
+    // Read once subscribed, so no change falls in between.
+    let mut current = default_sink().await?;
+    let _ = output.send(current).await;
+
+    while let Some(event) = events.next().await {
+        let event = event.map_err(|err| Error::Io("could not read from pactl subscribe", err))?;
+
+        // Event 'change' on sink #53, or on server #-1 when the default sink is another one.
+        if !(event.contains(" on sink ") || event.contains(" on server ")) {
+            continue;
+        }
+
+        let sink = default_sink().await?;
+        if sink != current {
+            current = sink;
+            let _ = output.send(current).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the volume of the default sink, between `0` and `1`.
+pub async fn set_volume(volume: f32) -> Result<(), Error> {
+    set_sink_volume(volume.clamp(0., 1.)).await
+}
+
+/// Adds `step` to the volume of the default sink, without going past 100%. A volume already
+/// raised past it elsewhere is kept, stepping up does not bring it down.
+pub async fn change_volume(step: f32) -> Result<(), Error> {
+    let current = default_sink().await?.volume;
+    let volume = stepped(current, step);
+    if volume == current {
+        return Ok(());
+    }
+
+    set_sink_volume(volume).await
+}
+
+/// Volume after a step, capped at 100% or at the current volume when above it.
+fn stepped(current: f32, step: f32) -> f32 {
+    if step > 0. {
+        (current + step).min(current.max(1.))
+    } else {
+        (current + step).max(0.)
+    }
+}
+
+async fn set_sink_volume(volume: f32) -> Result<(), Error> {
+    let percent = format!("{}%", (volume * 100.).round());
+    query(&["set-sink-volume", "@DEFAULT_SINK@", &percent]).await?;
+
+    Ok(())
+}
+
+pub async fn toggle_mute() -> Result<(), Error> {
+    query(&["set-sink-mute", "@DEFAULT_SINK@", "toggle"]).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_stop_at_full_volume() {
+        assert_eq!(stepped(0.5, 0.25), 0.75);
+        assert_eq!(stepped(0.95, 0.1), 1.);
+        assert_eq!(stepped(1., 0.05), 1.);
+        assert_eq!(stepped(0.05, -0.1), 0.);
+    }
+
+    #[test]
+    fn louder_volumes_are_kept() {
+        assert_eq!(stepped(1.5, 0.05), 1.5);
+        assert_eq!(stepped(1.5, -0.25), 1.25);
+    }
+}
@@ -0,0 +1,90 @@
+use iced::{
+    Subscription,
+    futures::{SinkExt, channel::mpsc::Sender},
+    stream,
+};
+use smol::stream::StreamExt;
+use zbus::{proxy, zvariant::Value};
+
+use crate::{Message, config::ThemeVariant};
+
+const APPEARANCE_NAMESPACE: &str = "org.freedesktop.appearance";
+const COLOR_SCHEME_KEY: &str = "color-scheme";
+
+#[proxy(
+    interface = "org.freedesktop.portal.Settings",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait PortalSettings {
+    fn read_one(&self, namespace: &str, key: &str) -> zbus::Result<zbus::zvariant::OwnedValue>;
+
+    #[zbus(signal)]
+    fn setting_changed(&self, namespace: &str, key: &str, value: Value<'_>) -> zbus::Result<()>;
+}
+
+/// Toggles the theme every time the daemon receives `SIGUSR1`.
+pub fn signal_subscription() -> Subscription<Message> {
+    Subscription::run(|| {
+        stream::channel(1, async |mut output| {
+            let mut signals = match async_signal::Signals::new([async_signal::Signal::Usr1]) {
+                Ok(signals) => signals,
+                Err(err) => {
+                    tracing::warn!("Could not listen for SIGUSR1: {err}");
+                    return;
+                }
+            };
+
+            while let Some(Ok(_)) = signals.next().await {
+                let _ = output.send(Message::ChangeTheme).await;
+            }
+        })
+    })
+}
+
+/// Follows the `color-scheme` preference exposed by xdg-desktop-portal.
+pub fn portal_subscription() -> Subscription<Message> {
+    Subscription::run(|| {
+        stream::channel(1, async |mut output| {
+            if let Err(err) = follow_portal(&mut output).await {
+                tracing::warn!("Could not follow the desktop color scheme: {err}");
+            }
+        })
+    })
+}
+
+async fn follow_portal(output: &mut Sender<Message>) -> zbus::Result<()> {
+    let connection = zbus::Connection::session().await?;
+    let settings = PortalSettingsProxy::new(&connection).await?;
+    let mut changes = settings.receive_setting_changed().await?;
+
+    let current = settings
+        .read_one(APPEARANCE_NAMESPACE, COLOR_SCHEME_KEY)
+        .await?;
+    if let Some(variant) = color_scheme(&current) {
+        let _ = output.send(Message::SetTheme(variant)).await;
+    }
+
+    while let Some(change) = changes.next().await {
+        let args = change.args()?;
+        if args.namespace != APPEARANCE_NAMESPACE || args.key != COLOR_SCHEME_KEY {
+            continue;
+        }
+
+        if let Some(variant) = color_scheme(&args.value) {
+            let _ = output.send(Message::SetTheme(variant)).await;
+        }
+    }
+
+    Ok(())
+}
+
+/// `1` prefers dark, `2` prefers light and `0` states no preference, which leaves the theme as is.
+fn color_scheme(value: &Value<'_>) -> Option<ThemeVariant> {
+    match value {
+        Value::Value(inner) => color_scheme(inner),
+        Value::U32(1) => Some(ThemeVariant::Dark),
+        Value::U32(2) => Some(ThemeVariant::Light),
+        _ => None,
+    }
+}
@@ -0,0 +1,493 @@
+use std::{collections::HashMap, path::Path};
+
+use iced::{
+    futures::{Sink, SinkExt, StreamExt, stream},
+    widget::image,
+};
+use zbus::{
+    fdo::{DBusProxy, RequestNameFlags, RequestNameReply},
+    interface,
+    message::Header,
+    object_server::SignalEmitter,
+    proxy,
+    proxy::CacheProperties,
+    zvariant::OwnedObjectPath,
+};
+
+use crate::{error::Error, icons, icons::Icon};
+
+pub mod menu;
+
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+const DEFAULT_ITEM_PATH: &str = "/StatusNotifierItem";
+
+/// Pixmaps closest to this size are preferred, the tray draws its icons at 24px.
+const PIXMAP_SIZE: i32 = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The item may be hidden, it has nothing to tell.
+    Passive,
+    Active,
+    NeedsAttention,
+}
+
+#[derive(Debug, Clone)]
+pub enum TrayIcon {
+    Themed(Icon),
+    Pixmap(image::Handle),
+}
+
+#[derive(Debug, Clone)]
+pub struct TrayItem {
+    /// Bus name and object path of the item, as registered with the watcher.
+    pub id: String,
+    pub title: String,
+    pub status: Status,
+    pub icon: Option<TrayIcon>,
+    /// Whether the item only supports showing its menu, not being activated.
+    pub item_is_menu: bool,
+    pub has_menu: bool,
+}
+
+#[proxy(interface = "org.kde.StatusNotifierItem")]
+trait StatusNotifierItem {
+    fn activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+    fn secondary_activate(&self, x: i32, y: i32) -> zbus::Result<()>;
+    fn scroll(&self, delta: i32, orientation: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn title(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn status(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn icon_name(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn icon_pixmap(&self) -> zbus::Result<Vec<(i32, i32, Vec<u8>)>>;
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> zbus::Result<String>;
+    #[zbus(property)]
+    fn menu(&self) -> zbus::Result<OwnedObjectPath>;
+    #[zbus(property)]
+    fn item_is_menu(&self) -> zbus::Result<bool>;
+
+    #[zbus(signal)]
+    fn new_icon(&self) -> zbus::Result<()>;
+    #[zbus(signal)]
+    fn new_title(&self) -> zbus::Result<()>;
+    #[zbus(signal)]
+    fn new_status(&self, status: String) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher"
+)]
+trait StatusNotifierWatcher {
+    fn register_status_notifier_host(&self, service: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn registered_status_notifier_items(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(signal)]
+    fn status_notifier_item_registered(&self, service: &str) -> zbus::Result<()>;
+    #[zbus(signal)]
+    fn status_notifier_item_unregistered(&self, service: &str) -> zbus::Result<()>;
+}
+
+/// The watcher side of the protocol, served when no other watcher runs on the session.
+#[derive(Debug, Default)]
+struct Watcher {
+    items: Vec<String>,
+}
+
+#[interface(name = "org.kde.StatusNotifierWatcher")]
+impl Watcher {
+    /// Items register either their bus name or, like libappindicator does, their object path.
+    async fn register_status_notifier_item(
+        &mut self,
+        service: &str,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) {
+        let id = match (service.starts_with('/'), header.sender()) {
+            (true, Some(sender)) => format!("{sender}{service}"),
+            (true, None) => return,
+            (false, _) => format!("{service}{DEFAULT_ITEM_PATH}"),
+        };
+
+        if !self.items.contains(&id) {
+            self.items.push(id.clone());
+            let _ = Self::status_notifier_item_registered(&emitter, &id).await;
+        }
+    }
+
+    async fn register_status_notifier_host(
+        &self,
+        _service: &str,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) {
+        let _ = Self::status_notifier_host_registered(&emitter).await;
+    }
+
+    #[zbus(property)]
+    fn registered_status_notifier_items(&self) -> Vec<String> {
+        self.items.clone()
+    }
+
+    #[zbus(property)]
+    fn is_status_notifier_host_registered(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn protocol_version(&self) -> i32 {
+        0
+    }
+
+    #[zbus(signal)]
+    async fn status_notifier_item_registered(
+        emitter: &SignalEmitter<'_>,
+        service: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn status_notifier_item_unregistered(
+        emitter: &SignalEmitter<'_>,
+        service: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn status_notifier_host_registered(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+}
+
+/// Splits an item id into its bus name and object path.
+fn split_id(id: &str) -> (&str, &str) {
+    match id.find('/') {
+        Some(i) => id.split_at(i),
+        None => (id, DEFAULT_ITEM_PATH),
+    }
+}
+
+async fn item_proxy(
+    connection: &zbus::Connection,
+    id: &str,
+) -> zbus::Result<StatusNotifierItemProxy<'static>> {
+    let (destination, path) = split_id(id);
+
+    // Items announce changes with their own signals instead of `PropertiesChanged`, so
+    // properties are always read from the item.
+    StatusNotifierItemProxy::builder(connection)
+        .destination(destination.to_string())?
+        .path(path.to_string())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+}
+
+/// Serves the watcher, unless another process already does. When it is served here, returns the
+/// task following the items, which keeps a clone of the connection, and so its names, until it
+/// is dropped.
+async fn serve_watcher(connection: &zbus::Connection) -> Result<Option<smol::Task<()>>, Error> {
+    connection
+        .object_server()
+        .at(WATCHER_PATH, Watcher::default())
+        .await?;
+
+    let reply = connection
+        .request_name_with_flags(WATCHER_NAME, RequestNameFlags::DoNotQueue.into())
+        .await;
+    if !matches!(
+        reply,
+        Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner)
+    ) {
+        connection
+            .object_server()
+            .remove::<Watcher, _>(WATCHER_PATH)
+            .await?;
+        return Ok(None);
+    }
+
+    let connection = connection.clone();
+    Ok(Some(smol::spawn(async move {
+        if let Err(err) = forget_vanished_items(&connection).await {
+            tracing::warn!("The tray watcher stopped following items: {err}");
+        }
+    })))
+}
+
+/// Unregisters the items of every client leaving the bus.
+async fn forget_vanished_items(connection: &zbus::Connection) -> Result<(), Error> {
+    let mut changes = DBusProxy::new(connection)
+        .await?
+        .receive_name_owner_changed()
+        .await?;
+    let watcher = connection
+        .object_server()
+        .interface::<_, Watcher>(WATCHER_PATH)
+        .await?;
+
+    while let Some(change) = changes.next().await {
+        let args = change.args()?;
+        if args.new_owner.is_some() {
+            continue;
+        }
+
+        let name = args.name.as_str();
+        let vanished = {
+            let mut watcher = watcher.get_mut().await;
+            let (vanished, kept) = std::mem::take(&mut watcher.items)
+                .into_iter()
+                .partition::<Vec<_>, _>(|id| split_id(id).0 == name);
+            watcher.items = kept;
+            vanished
+        };
+
+        for id in vanished {
+            Watcher::status_notifier_item_unregistered(watcher.signal_emitter(), &id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+fn status(status: &str) -> Status {
+    match status {
+        "Passive" => Status::Passive,
+        "NeedsAttention" => Status::NeedsAttention,
+        _ => Status::Active,
+    }
+}
+
+/// Icons in the item's own theme path come first, then the system theme, then the pixmaps.
+async fn icon(item: &StatusNotifierItemProxy<'_>) -> Option<TrayIcon> {
+    let name = item.icon_name().await.ok().filter(|n| !n.is_empty());
+    if let Some(name) = name {
+        let theme_path = item.icon_theme_path().await.ok().filter(|p| !p.is_empty());
+        let themed = smol::unblock(move || {
+            theme_path
+                .and_then(|path| icons::lookup_in(Path::new(&path), &name))
+                .or_else(|| icons::lookup(&name))
+        })
+        .await;
+
+        if let Some(icon) = themed {
+            return Some(TrayIcon::Themed(icon));
+        }
+    }
+
+    let pixmaps = item.icon_pixmap().await.ok()?;
+    let (width, height, argb) = pixmaps
+        .into_iter()
+        .filter(|(w, h, data)| is_argb32(*w, *h, data))
+        .min_by_key(|(w, _, _)| (*w - PIXMAP_SIZE).abs())?;
+
+    // Pixmaps are ARGB32 in network byte order.
+    let rgba = argb
+        .chunks_exact(4)
+        .flat_map(|p| [p[1], p[2], p[3], p[0]])
+        .collect::<Vec<_>>();
+
+    Some(TrayIcon::Pixmap(image::Handle::from_rgba(
+        width as u32,
+        height as u32,
+        rgba,
+    )))
+}
+
+/// Whether the pixels hold exactly the given size, in 4 bytes per pixel. The size comes from the
+/// item and may be anything.
+fn is_argb32(width: i32, height: i32, data: &[u8]) -> bool {
+    let (Ok(width), Ok(height)) = (usize::try_from(width), usize::try_from(height)) else {
+        return false;
+    };
+
+    width > 0
+        && height > 0
+        && width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(4))
+            == Some(data.len())
+}
+
+async fn fetch(item: &StatusNotifierItemProxy<'_>, id: &str) -> TrayItem {
+    let title = match item.title().await {
+        Ok(title) if !title.is_empty() => title,
+        _ => item.id().await.unwrap_or_default(),
+    };
+
+    TrayItem {
+        id: id.to_string(),
+        title,
+        status: item.status().await.map_or(Status::Active, |s| status(&s)),
+        icon: icon(item).await,
+        item_is_menu: item.item_is_menu().await.unwrap_or(false),
+        has_menu: item.menu().await.is_ok(),
+    }
+}
+
+/// Sends the id of the item every time it announces a new icon, title or status.
+async fn watch_item(
+    item: StatusNotifierItemProxy<'static>,
+    id: String,
+    changes: smol::channel::Sender<String>,
+) -> zbus::Result<()> {
+    let mut signals = stream::select_all([
+        item.receive_new_icon().await?.map(|_| ()).boxed(),
+        item.receive_new_title().await?.map(|_| ()).boxed(),
+        item.receive_new_status().await?.map(|_| ()).boxed(),
+    ]);
+
+    while signals.next().await.is_some() {
+        if changes.send(id.clone()).await.is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+enum HostEvent {
+    Registered(String),
+    Unregistered(String),
+    Changed(String),
+}
+
+/// Hosts the tray, serving the watcher as well when there is none. Sends the whole list of items
+/// every time one of them changes, in the order they were registered.
+pub async fn host(output: &mut (impl Sink<Vec<TrayItem>> + Unpin)) -> Result<(), Error> {
+    let connection = zbus::Connection::session().await?;
+    // Dropped with the host, for a new one to get the names back.
+    let serving = serve_watcher(&connection).await?;
+    if serving.is_none() {
+        tracing::info!("Another process serves {WATCHER_NAME}, only hosting the tray");
+    }
+
+    let host_name = format!("org.kde.StatusNotifierHost-{}", std::process::id());
+    connection.request_name(host_name.as_str()).await?;
+
+    let watcher = StatusNotifierWatcherProxy::new(&connection).await?;
+    let (changes_tx, changes_rx) = smol::channel::unbounded();
+    let mut events = stream::select_all([
+        watcher
+            .receive_status_notifier_item_registered()
+            .await?
+            .filter_map(|s| async move {
+                Some(HostEvent::Registered(s.args().ok()?.service.to_string()))
+            })
+            .boxed(),
+        watcher
+            .receive_status_notifier_item_unregistered()
+            .await?
+            .filter_map(|s| async move {
+                Some(HostEvent::Unregistered(s.args().ok()?.service.to_string()))
+            })
+            .boxed(),
+        changes_rx.map(HostEvent::Changed).boxed(),
+    ]);
+    watcher.register_status_notifier_host(&host_name).await?;
+
+    let mut items = Vec::<TrayItem>::new();
+    // Dropping a task stops listening to the signals of its item.
+    let mut watches = HashMap::<String, smol::Task<()>>::new();
+
+    let initial = watcher.registered_status_notifier_items().await?;
+    let initial = initial
+        .into_iter()
+        .map(HostEvent::Registered)
+        .collect::<Vec<_>>();
+    let mut pending = initial.into_iter();
+
+    loop {
+        let event = match pending.next() {
+            Some(event) => event,
+            None => {
+                let _ = output.send(items.clone()).await;
+                match events.next().await {
+                    Some(event) => event,
+                    None => return Err(Error::Stopped("the tray watcher")),
+                }
+            }
+        };
+
+        match event {
+            // Late signals of an item that is already gone.
+            HostEvent::Changed(id) if !items.iter().any(|i| i.id == id) => {}
+            HostEvent::Registered(id) | HostEvent::Changed(id) => {
+                let proxy = match item_proxy(&connection, &id).await {
+                    Ok(proxy) => proxy,
+                    Err(err) => {
+                        tracing::warn!("Could not reach tray item {id}: {err}");
+                        continue;
+                    }
+                };
+                let item = fetch(&proxy, &id).await;
+
+                match items.iter_mut().find(|i| i.id == id) {
+                    Some(current) => *current = item,
+                    None => items.push(item),
+                }
+
+                let changes = changes_tx.clone();
+                watches.entry(id.clone()).or_insert_with(|| {
+                    smol::spawn(async move {
+                        let _ = watch_item(proxy, id, changes).await;
+                    })
+                });
+            }
+            HostEvent::Unregistered(id) => {
+                items.retain(|i| i.id != id);
+                watches.remove(&id);
+            }
+        }
+    }
+}
+
+async fn session_item(id: &str) -> Result<StatusNotifierItemProxy<'static>, Error> {
+    let connection = zbus::Connection::session().await?;
+    Ok(item_proxy(&connection, id).await?)
+}
+
+/// Left click. The position is unknown to a layer surface, so the items get the origin.
+pub async fn activate(id: &str) -> Result<(), Error> {
+    Ok(session_item(id).await?.activate(0, 0).await?)
+}
+
+/// Middle click.
+pub async fn secondary_activate(id: &str) -> Result<(), Error> {
+    Ok(session_item(id).await?.secondary_activate(0, 0).await?)
+}
+
+pub async fn scroll(id: &str, delta: i32) -> Result<(), Error> {
+    Ok(session_item(id).await?.scroll(delta, "vertical").await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixmaps_must_hold_their_size() {
+        assert!(is_argb32(2, 3, &[0; 24]));
+        assert!(is_argb32(1, 1, &[0; 4]));
+
+        assert!(!is_argb32(2, 3, &[0; 23]));
+        assert!(!is_argb32(2, 3, &[0; 25]));
+        assert!(!is_argb32(0, 3, &[]));
+        assert!(!is_argb32(3, 0, &[]));
+    }
+
+    #[test]
+    fn pixmap_sizes_may_be_anything() {
+        assert!(!is_argb32(-1, -1, &[0; 4]));
+        assert!(!is_argb32(-2, 3, &[]));
+        assert!(!is_argb32(i32::MIN, i32::MIN, &[]));
+        assert!(!is_argb32(i32::MAX, i32::MAX, &[0; 16]));
+        // 65536 * 65536 * 4 wraps to 0 in 32 bits.
+        assert!(!is_argb32(65_536, 65_536, &[]));
+    }
+}
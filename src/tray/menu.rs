@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use zbus::{
+    proxy,
+    zvariant::{OwnedObjectPath, OwnedValue, Value},
+};
+
+use super::{item_proxy, split_id};
+use crate::error::Error;
+
+#[proxy(interface = "com.canonical.dbusmenu")]
+trait DbusMenu {
+    #[allow(clippy::type_complexity)]
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        recursion_depth: i32,
+        property_names: &[&str],
+    ) -> zbus::Result<(u32, (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>))>;
+
+    fn event(&self, id: i32, event_id: &str, data: &Value<'_>, timestamp: u32) -> zbus::Result<()>;
+
+    fn about_to_show(&self, id: i32) -> zbus::Result<bool>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toggle {
+    Checkmark(bool),
+    Radio(bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    pub id: i32,
+    pub label: String,
+    pub enabled: bool,
+    pub separator: bool,
+    pub toggle: Option<Toggle>,
+    pub children: Vec<MenuItem>,
+}
+
+/// The menu of a tray item, ready to be shown.
+#[derive(Debug, Clone)]
+pub struct Menu {
+    /// Id of the tray item owning the menu.
+    pub item: String,
+    pub path: OwnedObjectPath,
+    pub items: Vec<MenuItem>,
+}
+
+/// Variants may come wrapped in one another, the menu only cares about what is inside.
+fn inner<'a>(value: &'a Value<'a>) -> &'a Value<'a> {
+    match value {
+        Value::Value(inner) => self::inner(inner),
+        value => value,
+    }
+}
+
+/// Parses a `(ia{sv}av)` layout node, dropping invisible items.
+fn parse(value: &Value<'_>) -> Option<MenuItem> {
+    let Value::Structure(node) = inner(value) else {
+        return None;
+    };
+    let [
+        Value::I32(id),
+        Value::Dict(properties),
+        Value::Array(children),
+    ] = node.fields()
+    else {
+        return None;
+    };
+
+    let property = |key: &str| {
+        properties
+            .iter()
+            .find(|(k, _)| matches!(k, Value::Str(k) if k.as_str() == key))
+            .map(|(_, v)| inner(v))
+    };
+    let string = |key: &str| match property(key) {
+        Some(Value::Str(s)) => Some(s.as_str()),
+        _ => None,
+    };
+    let boolean = |key: &str, default: bool| match property(key) {
+        Some(Value::Bool(b)) => *b,
+        _ => default,
+    };
+
+    if !boolean("visible", true) {
+        return None;
+    }
+
+    let toggled = matches!(property("toggle-state"), Some(Value::I32(1)));
+    let toggle = match string("toggle-type") {
+        Some("checkmark") => Some(Toggle::Checkmark(toggled)),
+        Some("radio") => Some(Toggle::Radio(toggled)),
+        _ => None,
+    };
+
+    Some(MenuItem {
+        id: *id,
+        // Underscores mark mnemonics, doubled ones are literal.
+        label: string("label")
+            .unwrap_or_default()
+            .replace("__", "\0")
+            .replace('_', "")
+            .replace('\0', "_"),
+        enabled: boolean("enabled", true),
+        separator: string("type") == Some("separator"),
+        toggle,
+        children: children.iter().filter_map(parse).collect(),
+    })
+}
+
+async fn menu_proxy(
+    connection: &zbus::Connection,
+    item: &str,
+    path: OwnedObjectPath,
+) -> zbus::Result<DbusMenuProxy<'static>> {
+    DbusMenuProxy::builder(connection)
+        .destination(split_id(item).0.to_string())?
+        .path(path)?
+        .build()
+        .await
+}
+
+/// Fetches the whole menu of a tray item, `None` when it has none.
+pub async fn fetch(item: &str) -> Result<Option<Menu>, Error> {
+    let connection = zbus::Connection::session().await?;
+    let Ok(path) = item_proxy(&connection, item).await?.menu().await else {
+        return Ok(None);
+    };
+
+    let menu = menu_proxy(&connection, item, path.clone()).await?;
+    // Lets the application fill the menu lazily, failing to do so is not an error.
+    let _ = menu.about_to_show(0).await;
+    let (_, (_, _, children)) = menu.get_layout(0, -1, &[]).await?;
+
+    Ok(Some(Menu {
+        item: item.to_string(),
+        path,
+        items: children.iter().filter_map(|c| parse(c)).collect(),
+    }))
+}
+
+pub async fn clicked(item: &str, path: OwnedObjectPath, id: i32) -> Result<(), Error> {
+    let connection = zbus::Connection::session().await?;
+
+    Ok(menu_proxy(&connection, item, path)
+        .await?
+        .event(id, "clicked", &Value::I32(0), 0)
+        .await?)
+}
@@ -0,0 +1,163 @@
+use std::{io, pin::Pin, process::Stdio, time::Duration};
+
+use iced::futures::{Sink, SinkExt};
+use smol::{
+    Timer,
+    io::{AsyncRead, AsyncReadExt},
+    process::{Child, Command},
+};
+
+use crate::{
+    config::{AudioInput, VisualizerConfig},
+    error::Error,
+};
+
+mod spectrum;
+
+pub use spectrum::Spectrum;
+
+/// Inputs are read as signed 16-bit little-endian mono PCM at this rate.
+pub const SAMPLE_RATE: u32 = 48_000;
+const FRAME_RATE: u32 = 60;
+/// Samples quieter than this are digital silence.
+const SILENCE: f32 = 1. / 32768.;
+
+/// Audio being analyzed, open as long as it is read.
+struct Source {
+    reader: Pin<Box<dyn AsyncRead + Send>>,
+    /// Capture process of live inputs, killed with the source.
+    _child: Option<Child>,
+    /// Whether the input delivers samples in real time. Others are paced by the frame rate.
+    live: bool,
+}
+
+impl Source {
+    fn open(input: &AudioInput) -> Result<Self, Error> {
+        match input {
+            AudioInput::PipeWire => {
+                let mut command = Command::new("pw-record");
+                command
+                    .args(["--raw", "--format", "s16", "--channels", "1"])
+                    .args(["--rate", &SAMPLE_RATE.to_string()])
+                    // Records what the default output plays instead of the microphone.
+                    .args(["-P", "{ stream.capture.sink = true }", "-"])
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .kill_on_drop(true);
+
+                let mut child = command
+                    .spawn()
+                    .map_err(|err| Error::Io("could not run pw-record", err))?;
+                let stdout = child.stdout.take().ok_or(Error::Stopped("pw-record"))?;
+
+                Ok(Self {
+                    reader: Box::pin(stdout),
+                    _child: Some(child),
+                    live: true,
+                })
+            }
+            AudioInput::File(path) => {
+                // Opening a FIFO blocks until a writer shows up.
+                let file = std::fs::File::open(path)
+                    .map_err(|err| Error::Io("could not open the audio input", err))?;
+
+                Ok(Self {
+                    reader: Box::pin(smol::Unblock::new(file)),
+                    _child: None,
+                    live: false,
+                })
+            }
+        }
+    }
+}
+
+/// Analyzes the configured input, sending the height of every bar for each frame. Silence is
+/// sent once, so the waves go flat, and then not analyzed until the sound is back.
+pub async fn run(
+    config: &VisualizerConfig,
+    output: &mut (impl Sink<Vec<f32>> + Unpin),
+) -> Result<(), Error> {
+    let input = config.input.clone();
+    let mut source = smol::unblock(move || Source::open(&input)).await?;
+    let mut spectrum = Spectrum::new(config.bars, SAMPLE_RATE, config.smoothing, config.falloff);
+
+    let chunk = (SAMPLE_RATE / FRAME_RATE) as usize;
+    let mut bytes = vec![0; chunk * 2];
+    let mut samples = Vec::with_capacity(chunk);
+    let mut was_silent = true;
+
+    loop {
+        source
+            .reader
+            .read_exact(&mut bytes)
+            .await
+            .map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => Error::Stopped("the audio input"),
+                _ => Error::Io("could not read the audio input", err),
+            })?;
+
+        samples.clear();
+        samples.extend(
+            bytes
+                .chunks_exact(2)
+                .map(|b| f32::from(i16::from_le_bytes([b[0], b[1]])) / 32768.),
+        );
+
+        let quiet = samples.iter().all(|s| s.abs() < SILENCE);
+        if !(quiet && was_silent) {
+            spectrum.push(&samples);
+
+            let frame = spectrum.frame();
+            let is_silent = frame.iter().all(|f| *f == 0.);
+            if !(is_silent && was_silent) {
+                let _ = output.send(frame.to_vec()).await;
+            }
+            was_silent = is_silent;
+        }
+
+        if !source.live {
+            Timer::after(Duration::from_secs(1) / FRAME_RATE).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, f32::consts::PI, fs, iter, process};
+
+    use iced::futures::{StreamExt, channel::mpsc};
+
+    use super::*;
+
+    #[test]
+    fn waves_flatten_when_playback_pauses() {
+        // Half a second of a tone, then the silence of a paused player.
+        let tone = (0..SAMPLE_RATE / 2).map(|i| {
+            let phase = 2. * PI * 440. * i as f32 / SAMPLE_RATE as f32;
+            (phase.sin() * 16_000.) as i16
+        });
+        let pause = iter::repeat_n(0, SAMPLE_RATE as usize);
+        let pcm = tone
+            .chain(pause)
+            .flat_map(i16::to_le_bytes)
+            .collect::<Vec<_>>();
+
+        let path = env::temp_dir().join(format!("waybracelet-{}-pause.pcm", process::id()));
+        fs::write(&path, pcm).unwrap();
+        let config = VisualizerConfig {
+            falloff: 0.25,
+            input: AudioInput::File(path.clone()),
+            ..Default::default()
+        };
+
+        let (mut sender, receiver) = mpsc::unbounded();
+        let result = smol::block_on(run(&config, &mut sender));
+        drop(sender);
+        let _ = fs::remove_file(&path);
+
+        assert!(matches!(result, Err(Error::Stopped(_))), "{result:?}");
+        let frames = smol::block_on(receiver.collect::<Vec<_>>());
+        assert!(frames.iter().any(|f| f.iter().any(|h| *h > 0.5)));
+        assert!(frames.last().unwrap().iter().all(|h| *h == 0.));
+    }
+}
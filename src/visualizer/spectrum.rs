@@ -0,0 +1,194 @@
+use std::{collections::VecDeque, f32::consts::PI, ops::Range, sync::Arc};
+
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+
+/// Samples per transform, about 43ms at 48kHz. Enough resolution for the lowest bars.
+const FFT_SIZE: usize = 2048;
+const LOWEST_FREQUENCY: f32 = 50.;
+const HIGHEST_FREQUENCY: f32 = 12_000.;
+/// Level, relative to a full scale sine, at which a bar is empty.
+const FLOOR_DB: f32 = -60.;
+
+/// Turns PCM samples into bar heights between `0` and `1`, one bar per band of frequencies.
+pub struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    samples: VecDeque<f32>,
+    buffer: Vec<Complex<f32>>,
+    bands: Vec<Range<usize>>,
+    bars: Vec<f32>,
+    smoothing: f32,
+    falloff: f32,
+}
+
+impl Spectrum {
+    /// `smoothing` is the share of the previous height kept when a bar rises, `falloff` how much
+    /// a bar can drop from one frame to the next.
+    pub fn new(bars: usize, sample_rate: u32, smoothing: f32, falloff: f32) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+
+        Self {
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window,
+            samples: VecDeque::from(vec![0.; FFT_SIZE]),
+            buffer: vec![Complex::default(); FFT_SIZE],
+            bands: bands(bars, sample_rate),
+            bars: vec![0.; bars],
+            smoothing,
+            falloff,
+        }
+    }
+
+    /// Appends mono samples between `-1` and `1`, only the latest ones are analyzed.
+    pub fn push(&mut self, samples: &[f32]) {
+        let samples = &samples[samples.len().saturating_sub(FFT_SIZE)..];
+
+        self.samples.drain(..samples.len());
+        self.samples.extend(samples);
+    }
+
+    /// Heights of the bars for the latest samples.
+    pub fn frame(&mut self) -> &[f32] {
+        for ((slot, sample), weight) in self.buffer.iter_mut().zip(&self.samples).zip(&self.window)
+        {
+            *slot = Complex::new(sample * weight, 0.);
+        }
+        self.fft.process(&mut self.buffer);
+
+        // A full scale sine peaks at a quarter of the size through the Hann window.
+        let scale = 4. / FFT_SIZE as f32;
+        for (bar, band) in self.bars.iter_mut().zip(&self.bands) {
+            let magnitude = self.buffer[band.clone()]
+                .iter()
+                .map(|c| c.norm())
+                .fold(0., f32::max)
+                * scale;
+            let db = 20. * magnitude.max(f32::MIN_POSITIVE).log10();
+            let target = (1. - db / FLOOR_DB).clamp(0., 1.);
+
+            *bar = if target > *bar {
+                *bar * self.smoothing + target * (1. - self.smoothing)
+            } else {
+                (*bar - self.falloff).max(target)
+            };
+        }
+
+        &self.bars
+    }
+}
+
+/// Bins of each bar, spread logarithmically so low notes get as many bars as high ones. Every
+/// bar gets at least one bin.
+fn bands(bars: usize, sample_rate: u32) -> Vec<Range<usize>> {
+    let highest = HIGHEST_FREQUENCY.min(sample_rate as f32 / 2.);
+    let bin = |frequency: f32| (frequency * FFT_SIZE as f32 / sample_rate as f32).round() as usize;
+
+    let mut start = bin(LOWEST_FREQUENCY).max(1);
+    (1..=bars)
+        .map(|i| {
+            let frequency =
+                LOWEST_FREQUENCY * (highest / LOWEST_FREQUENCY).powf(i as f32 / bars as f32);
+            let end = bin(frequency).max(start + 1).min(FFT_SIZE / 2);
+            let band = start.min(end)..end;
+            start = end;
+            band
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn sine(frequency: f32) -> Vec<f32> {
+        (0..FFT_SIZE)
+            .map(|i| (2. * PI * frequency * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    /// Bar whose band holds the given frequency.
+    fn bar_of(bars: usize, frequency: f32) -> usize {
+        let bin = (frequency * FFT_SIZE as f32 / SAMPLE_RATE as f32).round() as usize;
+        bands(bars, SAMPLE_RATE)
+            .iter()
+            .position(|band| band.contains(&bin))
+            .unwrap()
+    }
+
+    #[test]
+    fn every_band_has_a_bin() {
+        for bars in 1..=128 {
+            let bands = bands(bars, SAMPLE_RATE);
+
+            assert_eq!(bands.len(), bars);
+            assert!(bands.iter().all(|b| !b.is_empty()), "{bars} bars");
+            assert!(
+                bands.windows(2).all(|w| w[0].end == w[1].start),
+                "{bars} bars"
+            );
+            assert!(bands[0].start >= 1, "{bars} bars");
+            assert!(bands[bars - 1].end <= FFT_SIZE / 2, "{bars} bars");
+        }
+    }
+
+    #[test]
+    fn a_sine_lights_its_band() {
+        for frequency in [100., 1_000., 8_000.] {
+            let mut spectrum = Spectrum::new(32, SAMPLE_RATE, 0., 1.);
+            spectrum.push(&sine(frequency));
+            let frame = spectrum.frame();
+
+            let lit = bar_of(32, frequency);
+            assert!(frame[lit] > 0.95, "{frequency}Hz: {frame:?}");
+            for (bar, height) in frame.iter().enumerate() {
+                if bar.abs_diff(lit) > 2 {
+                    assert!(*height < 0.5, "{frequency}Hz: {frame:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn silence_is_flat() {
+        let mut spectrum = Spectrum::new(24, SAMPLE_RATE, 0.5, 0.05);
+        spectrum.push(&[0.; FFT_SIZE]);
+
+        assert!(spectrum.frame().iter().all(|h| *h == 0.));
+    }
+
+    #[test]
+    fn bars_rise_smoothly_and_fall_steadily() {
+        let lit = bar_of(16, 1_000.);
+        let mut spectrum = Spectrum::new(16, SAMPLE_RATE, 0.5, 0.1);
+
+        let target = {
+            let mut unsmoothed = Spectrum::new(16, SAMPLE_RATE, 0., 1.);
+            unsmoothed.push(&sine(1_000.));
+            unsmoothed.frame()[lit]
+        };
+
+        spectrum.push(&sine(1_000.));
+        let first = spectrum.frame()[lit];
+        assert!((first - target * 0.5).abs() < 1e-4, "{first} for {target}");
+        let second = spectrum.frame()[lit];
+        assert!(
+            (second - target * 0.75).abs() < 1e-4,
+            "{second} for {target}"
+        );
+
+        spectrum.push(&[0.; FFT_SIZE]);
+        let mut height = second;
+        while height > 0. {
+            let next = spectrum.frame()[lit];
+            assert!(
+                (next - (height - 0.1).max(0.)).abs() < 1e-4,
+                "{next} after {height}"
+            );
+            height = next;
+        }
+    }
+}
@@ -1,8 +1,9 @@
 use std::ops::{Deref, DerefMut};
 
-use iced::window;
+use iced::{Task, window};
+use iced_layershell::reexport::OutputOption;
 
-use crate::features::Feature;
+use crate::{Message, config::Config, features::Feature};
 
 #[derive(Debug, Clone)]
 pub(super) struct Window<T>
@@ -32,3 +33,160 @@ where
         &mut self.view
     }
 }
+
+impl<T> Window<T>
+where
+    T: Feature,
+{
+    /// Replaces the layer surface with a new one built from the current
+    /// [`Feature::layer_settings`], keeping the feature state.
+    pub fn reopen(&mut self) -> Task<Message> {
+        let old_id = std::mem::replace(&mut self.id, window::Id::unique());
+
+        Task::done(Message::RemoveWindow(old_id)).chain(Task::done(Message::NewLayerShell {
+            settings: self.view.layer_settings(),
+            id: self.id,
+        }))
+    }
+
+    /// Rebuilds the surface when its target output changes or when the output it was shown on,
+    /// `removed`, is gone. Surfaces placed by the compositor are rebuilt on any removal since
+    /// there is no telling where they were.
+    pub fn retarget(&mut self, outputs: &[String], removed: Option<&str>) -> Task<Message> {
+        let stranded =
+            removed.is_some_and(|removed| self.view.output().is_none_or(|o| o == removed));
+
+        if self.view.retarget(outputs) || stranded {
+            self.reopen()
+        } else {
+            Task::none()
+        }
+    }
+
+    pub fn reconfigure(&mut self, config: &Config) -> Task<Message> {
+        if self.view.reconfigure(config) {
+            self.reopen()
+        } else {
+            Task::none()
+        }
+    }
+}
+
+/// A feature drawn on a layer surface per output in [`Feature::outputs`], every surface showing
+/// the same state.
+#[derive(Debug, Clone)]
+pub(super) struct Mirrored<T>
+where
+    T: Feature,
+{
+    /// Surfaces with the output each one is pinned to.
+    pub surfaces: Vec<(window::Id, Option<String>)>,
+    pub view: T,
+}
+
+impl<T> Deref for Mirrored<T>
+where
+    T: Feature,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.view
+    }
+}
+
+impl<T> DerefMut for Mirrored<T>
+where
+    T: Feature,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.view
+    }
+}
+
+impl<T> Mirrored<T>
+where
+    T: Feature,
+{
+    pub fn open(view: T) -> (Self, Task<Message>) {
+        let mut mirrored = Self {
+            surfaces: Vec::new(),
+            view,
+        };
+        let task = mirrored.reopen();
+
+        (mirrored, task)
+    }
+
+    pub fn contains(&self, id: window::Id) -> bool {
+        self.surfaces.iter().any(|(i, _)| *i == id)
+    }
+
+    /// Replaces every surface with one per output the feature is now shown on.
+    pub fn reopen(&mut self) -> Task<Message> {
+        let removed = Task::batch(
+            self.surfaces
+                .drain(..)
+                .map(|(id, _)| Task::done(Message::RemoveWindow(id))),
+        );
+
+        let settings = self.view.layer_settings();
+        self.surfaces = self
+            .view
+            .outputs()
+            .into_iter()
+            .map(|output| (window::Id::unique(), output))
+            .collect();
+        let opened = self.surfaces.iter().map(|(id, output)| {
+            let mut settings = settings.clone();
+            settings.output_option = output
+                .clone()
+                .map_or(OutputOption::None, OutputOption::OutputName);
+
+            Task::done(Message::NewLayerShell { settings, id: *id })
+        });
+
+        removed.chain(Task::batch(opened))
+    }
+
+    /// Rebuilds the surfaces when `change` asks for it or changes the outputs, or when one they
+    /// were shown on, `removed`, is gone.
+    fn rebuild_if(
+        &mut self,
+        removed: Option<&str>,
+        change: impl FnOnce(&mut T) -> bool,
+    ) -> Task<Message> {
+        let before = self.view.outputs();
+        let asked = change(&mut self.view);
+
+        let stranded = removed.is_some_and(|removed| {
+            self.surfaces
+                .iter()
+                .any(|(_, output)| output.as_deref().is_none_or(|o| o == removed))
+        });
+
+        if asked || before != self.view.outputs() || stranded {
+            self.reopen()
+        } else {
+            Task::none()
+        }
+    }
+
+    pub fn update(&mut self, message: T::InnerMessage) -> Task<Message> {
+        let mut task = Task::none();
+        let rebuild = self.rebuild_if(None, |view| {
+            task = view.update(message);
+            false
+        });
+
+        Task::batch([task, rebuild])
+    }
+
+    pub fn retarget(&mut self, outputs: &[String], removed: Option<&str>) -> Task<Message> {
+        self.rebuild_if(removed, |view| view.retarget(outputs))
+    }
+
+    pub fn reconfigure(&mut self, config: &Config) -> Task<Message> {
+        self.rebuild_if(None, |view| view.reconfigure(config))
+    }
+}